@@ -1,14 +1,17 @@
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 use walkdir::WalkDir;
 
-use crate::image::formats::InputFormat;
-use crate::image::processor::{ImageProcessor, ProcessingOptions, ProcessingResult};
+use crate::image::formats::{InputFormat, OutputFormat};
+use crate::image::inspect::MetadataReport;
+use crate::image::pages;
+use crate::image::pdf::{self, PdfOptions, PdfPage};
+use crate::image::processor::{ImageProcessor, ProcessError, ProcessingOptions, ProcessingResult};
 
 /// Batch processing statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +25,10 @@ pub struct BatchStats {
     pub overall_reduction_percent: f64,
     pub average_reduction_percent: f64,
     pub median_reduction_percent: f64,
+    /// PDF the batch was bundled into, if any
+    pub pdf_path: Option<String>,
+    /// Why the PDF could not be written; the processed files are kept
+    pub pdf_error: Option<String>,
 }
 
 /// Progress update event
@@ -112,11 +119,74 @@ pub async fn process_batch(
     output_dir: String,
     options: ProcessingOptions,
 ) -> Result<BatchStats, String> {
+    let results: Vec<ProcessingResult> =
+        run_batch(&app, &input_paths, Path::new(&output_dir), &options, None)?
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect();
+
+    // Calculate statistics
+    let stats = calculate_batch_stats(&results);
+
+    // Emit completion event
+    let _ = app.emit("processing-complete", &stats);
+
+    Ok(stats)
+}
+
+/// Process multiple images in batch, then bundle the outputs into one PDF
+///
+/// Pages follow the input order; failed images are left out. The PDF name is
+/// reduced to a bare file name in the output directory. A PDF that cannot be
+/// written is reported in the stats instead of failing the whole batch.
+#[tauri::command]
+pub async fn process_batch_pdf(
+    app: AppHandle,
+    input_paths: Vec<String>,
+    output_dir: String,
+    options: ProcessingOptions,
+    pdf_options: PdfOptions,
+) -> Result<BatchStats, String> {
+    let output_dir = Path::new(&output_dir);
+    let (results, pages): (Vec<_>, Vec<_>) =
+        run_batch(&app, &input_paths, output_dir, &options, Some(&pdf_options))?
+            .into_iter()
+            .unzip();
+
+    let pages: Vec<PdfPage> = pages.into_iter().flatten().collect();
+    let pdf_path = output_dir.join(pdf::file_name(&pdf_options.file_name));
+    let stats = match pdf::write_pdf(&pages, &pdf_path, &pdf_options) {
+        Ok(()) => BatchStats {
+            pdf_path: Some(pdf_path.to_string_lossy().to_string()),
+            ..calculate_batch_stats(&results)
+        },
+        Err(e) => BatchStats {
+            pdf_error: Some(format!("Failed to write PDF: {}", e)),
+            ..calculate_batch_stats(&results)
+        },
+    };
+
+    // Emit completion event
+    let _ = app.emit("processing-complete", &stats);
+
+    Ok(stats)
+}
+
+/// Process every input in parallel, emitting progress and per-file results
+///
+/// Results are returned in input order, each with its PDF pages when a bundle
+/// is built.
+fn run_batch(
+    app: &AppHandle,
+    input_paths: &[String],
+    output_dir_path: &Path,
+    options: &ProcessingOptions,
+    pdf_options: Option<&PdfOptions>,
+) -> Result<Vec<(ProcessingResult, Vec<PdfPage>)>, String> {
     let total_files = input_paths.len();
-    let output_dir_path = PathBuf::from(&output_dir);
 
     // Ensure output directory exists
-    std::fs::create_dir_all(&output_dir_path)
+    std::fs::create_dir_all(output_dir_path)
         .map_err(|e| format!("Failed to create output directory: {}", e))?;
 
     // Calculate optimal thread count to balance CPU and I/O
@@ -132,7 +202,7 @@ pub async fn process_batch(
     let processed_count = Arc::new(AtomicUsize::new(0));
 
     // Process images in parallel with controlled concurrency
-    let results: Vec<ProcessingResult> = pool.install(|| {
+    let results: Vec<(ProcessingResult, Vec<PdfPage>)> = pool.install(|| {
         input_paths
             .par_iter()
             .enumerate()
@@ -147,17 +217,24 @@ pub async fn process_batch(
                     output_dir_path.join(format!("{}.{}", file_stem, options.format.extension()));
 
                 // Process the image
-                let result = match ImageProcessor::process_image(input, &output_path, &options) {
-                    Ok(result) => result,
-                    Err(e) => ProcessingResult {
-                        original_path: input_path.clone(),
-                        output_path: output_path.to_string_lossy().to_string(),
-                        original_size: 0,
-                        output_size: 0,
-                        reduction_percent: 0.0,
-                        success: false,
-                        error: Some(e.to_string()),
-                    },
+                let processed = process_file(input, &output_path, options, pdf_options);
+                let (result, pages) = match processed {
+                    Ok(processed) => processed,
+                    Err(e) => (
+                        ProcessingResult {
+                            original_path: input_path.clone(),
+                            output_path: output_path.to_string_lossy().to_string(),
+                            original_size: 0,
+                            output_size: 0,
+                            reduction_percent: 0.0,
+                            success: false,
+                            error: Some(e.to_string()),
+                            removed_metadata: Vec::new(),
+                            warnings: Vec::new(),
+                            page_outputs: Vec::new(),
+                        },
+                        Vec::new(),
+                    ),
                 };
 
                 // Update progress counter atomically
@@ -177,18 +254,41 @@ pub async fn process_batch(
                 // Emit individual file result
                 let _ = app.emit("processing-result", &result);
 
-                result
+                (result, pages)
             })
             .collect()
     });
 
-    // Calculate statistics
-    let stats = calculate_batch_stats(&results);
+    Ok(results)
+}
 
-    // Emit completion event
-    let _ = app.emit("processing-complete", &stats);
+/// Process one input, keeping its decoded pages as PDF pages when a bundle is built
+///
+/// Inputs whose pixels were not kept (JPEG recompressed to JPEG XL) are rendered
+/// from the source instead.
+fn process_file(
+    input: &Path,
+    output_path: &Path,
+    options: &ProcessingOptions,
+    pdf_options: Option<&PdfOptions>,
+) -> Result<(ProcessingResult, Vec<PdfPage>), ProcessError> {
+    let Some(pdf_options) = pdf_options else {
+        return Ok((
+            ImageProcessor::process_image(input, output_path, options)?,
+            Vec::new(),
+        ));
+    };
 
-    Ok(stats)
+    let (result, images) = ImageProcessor::process_image_with_pages(input, output_path, options)?;
+    let pages = if images.is_empty() {
+        pdf::render_pages(input, options, pdf_options)?
+    } else {
+        images
+            .iter()
+            .map(|img| PdfPage::new(img, pdf_options))
+            .collect::<Result<_, _>>()?
+    };
+    Ok((result, pages))
 }
 
 /// Calculate batch processing statistics
@@ -243,6 +343,8 @@ fn calculate_batch_stats(results: &[ProcessingResult]) -> BatchStats {
         overall_reduction_percent,
         average_reduction_percent,
         median_reduction_percent,
+        pdf_path: None,
+        pdf_error: None,
     }
 }
 
@@ -253,7 +355,10 @@ pub fn get_image_info(path: String) -> Result<ImageInfo, String> {
 
     let metadata = std::fs::metadata(path).map_err(|e| format!("Failed to read file: {}", e))?;
 
-    let img = image::open(path).map_err(|e| format!("Failed to open image: {}", e))?;
+    let img =
+        ImageProcessor::load_image(path).map_err(|e| format!("Failed to open image: {}", e))?;
+    let page_count =
+        pages::page_count(path).map_err(|e| format!("Failed to count pages: {}", e))?;
 
     Ok(ImageInfo {
         path: path.to_string_lossy().to_string(),
@@ -264,9 +369,34 @@ pub fn get_image_info(path: String) -> Result<ImageInfo, String> {
             .extension()
             .map(|e| e.to_string_lossy().to_string())
             .unwrap_or_default(),
+        page_count,
     })
 }
 
+/// Get the metadata stored in an image (EXIF, XMP, IPTC, ICC, DPI)
+///
+/// Only file headers are read, so this is cheap enough to run before a batch.
+#[tauri::command]
+pub fn get_image_metadata(path: String) -> Result<MetadataReport, String> {
+    MetadataReport::read(&path).map_err(|e| format!("Failed to read metadata: {}", e))
+}
+
+/// Get the formats this build can convert from and to
+#[tauri::command]
+pub fn get_supported_formats() -> SupportedFormats {
+    SupportedFormats {
+        input_extensions: InputFormat::EXTENSIONS
+            .into_iter()
+            .filter(|ext| InputFormat::is_supported(ext))
+            .map(String::from)
+            .collect(),
+        output_formats: OutputFormat::ALL
+            .into_iter()
+            .filter(OutputFormat::is_available)
+            .collect(),
+    }
+}
+
 /// Image information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageInfo {
@@ -275,4 +405,14 @@ pub struct ImageInfo {
     pub height: u32,
     pub size_bytes: u64,
     pub format: String,
+    /// Number of pages (multi-page TIFF); 1 for other images
+    pub page_count: usize,
+}
+
+/// Formats compiled into this build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupportedFormats {
+    /// File extensions that can be opened
+    pub input_extensions: Vec<String>,
+    pub output_formats: Vec<OutputFormat>,
 }
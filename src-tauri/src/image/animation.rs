@@ -0,0 +1,561 @@
+use gif::{DisposalMethod, Repeat};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, Frames, RgbaImage};
+use std::io::Cursor;
+use std::path::Path;
+
+use super::formats::InputFormat;
+use super::metadata::ImageMetadata;
+use super::processor::{CompressionType, ProcessError, ProcessingOptions};
+use super::quantize::{IndexedImage, Palette};
+use super::xmp;
+
+/// Frames of an animated image, each a full composited canvas
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    /// How often the animation plays; 0 = forever
+    pub loop_count: u16,
+}
+
+pub struct AnimationFrame {
+    pub image: DynamicImage,
+    pub delay_ms: u32,
+}
+
+impl Animation {
+    /// Transform every frame in order, keeping delays
+    pub fn map_frames(
+        self,
+        mut f: impl FnMut(DynamicImage) -> Result<DynamicImage, ProcessError>,
+    ) -> Result<Self, ProcessError> {
+        Ok(Self {
+            frames: self
+                .frames
+                .into_iter()
+                .map(|frame| {
+                    Ok(AnimationFrame {
+                        image: f(frame.image)?,
+                        delay_ms: frame.delay_ms,
+                    })
+                })
+                .collect::<Result<_, ProcessError>>()?,
+            loop_count: self.loop_count,
+        })
+    }
+}
+
+/// Decode all frames of an animated GIF, WebP or PNG (APNG)
+///
+/// Returns `None` for other formats and for files with a single frame.
+pub fn decode(path: &Path) -> Result<Option<Animation>, ProcessError> {
+    let Some(format @ (InputFormat::Gif | InputFormat::WebP | InputFormat::Png)) =
+        InputFormat::from_path(path)
+    else {
+        return Ok(None);
+    };
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    decode_bytes(&data, format)
+}
+
+fn decode_bytes(data: &[u8], format: InputFormat) -> Result<Option<Animation>, ProcessError> {
+    // Stills are told apart after two frames, before every frame is decoded
+    let Some(probe) = frames(data, format)? else {
+        return Ok(None);
+    };
+    if probe.take(2).count() < 2 {
+        return Ok(None);
+    }
+
+    let Some(frames) = frames(data, format)? else {
+        return Ok(None);
+    };
+    let frames = frames
+        .collect_frames()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let loop_count = match format {
+        InputFormat::Gif => gif_loop_count(data),
+        InputFormat::WebP => webp_loop_count(data),
+        _ => png_loop_count(data),
+    };
+
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            AnimationFrame {
+                delay_ms: numer / denom.max(1),
+                image: DynamicImage::ImageRgba8(frame.into_buffer()),
+            }
+        })
+        .collect();
+
+    Ok(Some(Animation { frames, loop_count }))
+}
+
+/// Frame iterator of an animated GIF, WebP or PNG; `None` when the file cannot animate
+fn frames(data: &[u8], format: InputFormat) -> Result<Option<Frames<'_>>, ProcessError> {
+    let read_error = |e: image::ImageError| ProcessError::ReadError(e.to_string());
+
+    let frames = match format {
+        InputFormat::Gif => GifDecoder::new(Cursor::new(data))
+            .map_err(read_error)?
+            .into_frames(),
+        InputFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(data)).map_err(read_error)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        InputFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(data)).map_err(read_error)?;
+            if !decoder.is_apng().map_err(read_error)? {
+                return Ok(None);
+            }
+            decoder.apng().map_err(read_error)?.into_frames()
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(frames))
+}
+
+/// Encode as GIF, with one palette shared by all frames
+///
+/// GIF transparency is on or off, so alpha is thresholded at half.
+pub fn encode_gif(
+    animation: &Animation,
+    options: &ProcessingOptions,
+) -> Result<Vec<u8>, ProcessError> {
+    let write_error = |e: gif::EncodingError| ProcessError::WriteError(e.to_string());
+
+    let frames: Vec<RgbaImage> = animation
+        .frames
+        .iter()
+        .map(|frame| {
+            let mut rgba = frame.image.to_rgba8();
+            for pixel in rgba.pixels_mut() {
+                pixel.0 = if pixel[3] < 128 {
+                    [0; 4]
+                } else {
+                    [pixel[0], pixel[1], pixel[2], 255]
+                };
+            }
+            rgba
+        })
+        .collect();
+    let first = frames
+        .first()
+        .ok_or_else(|| ProcessError::WriteError("Animation has no frames".to_string()))?;
+    let (width, height) = match (u16::try_from(first.width()), u16::try_from(first.height())) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(ProcessError::WriteError(format!(
+                "{}x{} exceeds the GIF size limit of 65535 pixels",
+                first.width(),
+                first.height()
+            )))
+        }
+    };
+
+    let palette = Palette::build(&frames.iter().collect::<Vec<_>>(), options.quality);
+    let indexed: Vec<IndexedImage> = frames
+        .iter()
+        .map(|frame| palette.index(frame, options.quality, options.dithering))
+        .collect();
+    let colors: Vec<u8> = palette
+        .colors()
+        .iter()
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+
+    let mut out = Vec::new();
+    {
+        let mut encoder =
+            gif::Encoder::new(&mut out, width, height, &colors).map_err(write_error)?;
+        // Without a loop extension a GIF plays once; its count is of repeats, not plays
+        match animation.loop_count {
+            0 => encoder.set_repeat(Repeat::Infinite).map_err(write_error)?,
+            1 => {}
+            plays => encoder
+                .set_repeat(Repeat::Finite(plays - 1))
+                .map_err(write_error)?,
+        }
+
+        for (frame, image) in animation.frames.iter().zip(indexed) {
+            encoder
+                .write_frame(&gif::Frame {
+                    width,
+                    height,
+                    // Frames are whole canvases, so each replaces the last
+                    dispose: DisposalMethod::Background,
+                    transparent: image.transparent,
+                    delay: u16::try_from(frame.delay_ms.div_ceil(10)).unwrap_or(u16::MAX),
+                    buffer: image.indices.into(),
+                    ..gif::Frame::default()
+                })
+                .map_err(write_error)?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Encode as animated WebP with the quality/compression options
+pub fn encode_webp(
+    animation: &Animation,
+    options: &ProcessingOptions,
+) -> Result<Vec<u8>, ProcessError> {
+    let first = animation
+        .frames
+        .first()
+        .ok_or_else(|| ProcessError::WriteError("Animation has no frames".to_string()))?;
+    let (width, height) = (first.image.width(), first.image.height());
+
+    let lossless = options.compression == CompressionType::Lossless;
+    let mut config = webp::WebPConfig::new()
+        .map_err(|_| ProcessError::WriteError("Cannot configure WebP encoder".to_string()))?;
+    config.lossless = i32::from(lossless);
+    config.alpha_compression = i32::from(!lossless);
+    config.quality = f32::from(options.quality);
+
+    let encoder = WebPAnimWriter::new(width, height, animation.loop_count)?;
+    // Frames are placed by start time
+    let mut timestamp: i32 = 0;
+    for frame in &animation.frames {
+        encoder.add(&frame.image.to_rgba8(), timestamp, &config)?;
+        timestamp = timestamp.saturating_add(frame.delay_ms.try_into().unwrap_or(i32::MAX));
+    }
+    encoder.finish(timestamp)
+}
+
+/// libwebp's animation encoder
+///
+/// `webp::AnimEncoder` closes the animation at timestamp 0, which loses the
+/// duration of the last frame; this closes it at the end of the last frame.
+struct WebPAnimWriter {
+    encoder: *mut libwebp_sys::WebPAnimEncoder,
+    width: u32,
+    height: u32,
+}
+
+impl WebPAnimWriter {
+    fn new(width: u32, height: u32, loop_count: u16) -> Result<Self, ProcessError> {
+        let failed =
+            || ProcessError::WriteError("Cannot create WebP animation encoder".to_string());
+        let abi = libwebp_sys::WEBP_MUX_ABI_VERSION as i32;
+
+        // SAFETY: the options are initialized by libwebp before being read
+        let encoder = unsafe {
+            let mut options = std::mem::MaybeUninit::uninit();
+            if libwebp_sys::WebPAnimEncoderOptionsInitInternal(options.as_mut_ptr(), abi) == 0 {
+                return Err(failed());
+            }
+            let mut options: libwebp_sys::WebPAnimEncoderOptions = options.assume_init();
+            options.anim_params.loop_count = i32::from(loop_count);
+            libwebp_sys::WebPAnimEncoderNewInternal(width as i32, height as i32, &options, abi)
+        };
+        if encoder.is_null() {
+            return Err(failed());
+        }
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+        })
+    }
+
+    /// Add a frame shown from `timestamp_ms`
+    fn add(
+        &self,
+        frame: &RgbaImage,
+        timestamp_ms: i32,
+        config: &webp::WebPConfig,
+    ) -> Result<(), ProcessError> {
+        let mut picture = libwebp_sys::WebPPicture::new()
+            .map_err(|_| ProcessError::WriteError("Cannot allocate WebP frame".to_string()))?;
+        picture.use_argb = 1;
+        picture.width = self.width as i32;
+        picture.height = self.height as i32;
+
+        // SAFETY: the buffer holds width x height RGBA pixels and outlives the calls;
+        // libwebp copies the pixels into the picture, which is freed before returning
+        let added = unsafe {
+            let imported = libwebp_sys::WebPPictureImportRGBA(
+                &mut picture,
+                frame.as_raw().as_ptr(),
+                self.width as i32 * 4,
+            ) != 0;
+            let added = imported
+                && libwebp_sys::WebPAnimEncoderAdd(
+                    self.encoder,
+                    &mut picture,
+                    timestamp_ms,
+                    config,
+                ) != 0;
+            libwebp_sys::WebPPictureFree(&mut picture);
+            added
+        };
+        if !added {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    /// Close the animation at `end_ms` and assemble the file
+    fn finish(self, end_ms: i32) -> Result<Vec<u8>, ProcessError> {
+        // SAFETY: a null frame marks the end; the assembled data is copied and then freed
+        unsafe {
+            if libwebp_sys::WebPAnimEncoderAdd(
+                self.encoder,
+                std::ptr::null_mut(),
+                end_ms,
+                std::ptr::null(),
+            ) == 0
+            {
+                return Err(self.error());
+            }
+            let mut data = libwebp_sys::WebPData::default();
+            if libwebp_sys::WebPAnimEncoderAssemble(self.encoder, &mut data) == 0 {
+                return Err(self.error());
+            }
+            let encoded = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+            libwebp_sys::WebPDataClear(&mut data);
+            Ok(encoded)
+        }
+    }
+
+    fn error(&self) -> ProcessError {
+        // SAFETY: libwebp returns null or a string owned by the encoder
+        let message = unsafe {
+            let message = libwebp_sys::WebPAnimEncoderGetError(self.encoder);
+            (!message.is_null()).then(|| std::ffi::CStr::from_ptr(message).to_string_lossy())
+        };
+        ProcessError::WriteError(format!(
+            "WebP animation encoding failed: {}",
+            message.as_deref().unwrap_or("unknown error")
+        ))
+    }
+}
+
+impl Drop for WebPAnimWriter {
+    fn drop(&mut self) {
+        // SAFETY: the encoder was created by WebPAnimEncoderNewInternal and is deleted once
+        unsafe { libwebp_sys::WebPAnimEncoderDelete(self.encoder) }
+    }
+}
+
+/// Encode as animated PNG (APNG), keeping the metadata PNG can carry
+pub fn encode_png(
+    animation: &Animation,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>, ProcessError> {
+    let write_error = |e: png::EncodingError| ProcessError::WriteError(e.to_string());
+
+    let first = animation
+        .frames
+        .first()
+        .ok_or_else(|| ProcessError::WriteError("Animation has no frames".to_string()))?;
+    let (width, height) = (first.image.width(), first.image.height());
+    let exif = metadata.exif_block()?;
+
+    let mut out = Vec::new();
+    {
+        let mut info = png::Info::with_size(width, height);
+        info.color_type = png::ColorType::Rgba;
+        info.bit_depth = png::BitDepth::Eight;
+        info.icc_profile = metadata.icc_profile.as_deref().map(Into::into);
+        info.exif_metadata = exif.map(Into::into);
+
+        let mut encoder = png::Encoder::with_info(&mut out, info).map_err(write_error)?;
+        encoder
+            .set_animated(
+                animation.frames.len() as u32,
+                u32::from(animation.loop_count),
+            )
+            .map_err(write_error)?;
+
+        let mut writer = encoder.write_header().map_err(write_error)?;
+        for frame in &animation.frames {
+            let (numer, denom) = png_delay(frame.delay_ms);
+            writer.set_frame_delay(numer, denom).map_err(write_error)?;
+            writer
+                .write_image_data(frame.image.to_rgba8().as_raw())
+                .map_err(write_error)?;
+        }
+        writer.finish().map_err(write_error)?;
+    }
+
+    match metadata.xmp_packet() {
+        Some(packet) => xmp::embed_in_png(&out, packet),
+        None => Ok(out),
+    }
+}
+
+/// Frame delay as the 16-bit fraction of a second APNG stores
+fn png_delay(delay_ms: u32) -> (u16, u16) {
+    match u16::try_from(delay_ms) {
+        Ok(ms) => (ms, 1000),
+        // Over a minute; whole seconds are precise enough
+        Err(_) => (u16::try_from(delay_ms / 1000).unwrap_or(u16::MAX), 1),
+    }
+}
+
+/// Plays recorded in the NETSCAPE2.0 extension (absent = once)
+///
+/// The decoder reads the application extensions ahead of the first frame.
+fn gif_loop_count(data: &[u8]) -> u16 {
+    match gif::DecodeOptions::new().read_info(Cursor::new(data)) {
+        Ok(decoder) => match decoder.repeat() {
+            Repeat::Infinite => 0,
+            Repeat::Finite(0) => 1,
+            Repeat::Finite(repeats) => repeats.saturating_add(1),
+        },
+        Err(_) => 1,
+    }
+}
+
+/// Loop count from the ANIM chunk (absent = forever)
+fn webp_loop_count(data: &[u8]) -> u16 {
+    let mut pos = 12;
+    while let Some(header) = data.get(pos..pos + 8) {
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if &header[0..4] == b"ANIM" {
+            return data
+                .get(pos + 12..pos + 14)
+                .map_or(0, |count| u16::from_le_bytes([count[0], count[1]]));
+        }
+        // Chunks are padded to an even size
+        pos += 8 + size + (size & 1);
+    }
+    0
+}
+
+/// Plays recorded in the acTL chunk (0 = forever)
+fn png_loop_count(data: &[u8]) -> u16 {
+    let mut pos = 8;
+    while let Some(header) = data.get(pos..pos + 8) {
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        match &header[4..8] {
+            b"acTL" => {
+                // num_frames, then num_plays
+                return data.get(pos + 12..pos + 16).map_or(0, |plays| {
+                    let plays = u32::from_be_bytes([plays[0], plays[1], plays[2], plays[3]]);
+                    u16::try_from(plays).unwrap_or(0)
+                });
+            }
+            b"IDAT" => break,
+            _ => {}
+        }
+        // Length, type, data and CRC
+        pos += 12 + size;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops::FilterType;
+    use image::Rgba;
+
+    fn animation(loop_count: u16) -> Animation {
+        let frame = |color: [u8; 4], delay_ms| AnimationFrame {
+            image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 6, Rgba(color))),
+            delay_ms,
+        };
+        Animation {
+            frames: vec![frame([255, 0, 0, 255], 100), frame([0, 0, 255, 255], 250)],
+            loop_count,
+        }
+    }
+
+    #[test]
+    fn test_gif_round_trip_keeps_frames_and_loops() {
+        for loop_count in [0, 1, 3] {
+            let gif = encode_gif(&animation(loop_count), &ProcessingOptions::default()).unwrap();
+            let decoded = decode_bytes(&gif, InputFormat::Gif).unwrap().unwrap();
+
+            assert_eq!(decoded.loop_count, loop_count);
+            let delays: Vec<u32> = decoded.frames.iter().map(|f| f.delay_ms).collect();
+            assert_eq!(delays, [100, 250]);
+            let first = decoded.frames[0].image.to_rgba8();
+            assert_eq!(first.dimensions(), (8, 6));
+            assert!(first.get_pixel(0, 0)[0] > 200);
+        }
+    }
+
+    #[test]
+    fn test_webp_round_trip_keeps_frames_and_loops() {
+        let resized = animation(2)
+            .map_frames(|img| Ok(img.resize_exact(4, 3, FilterType::Triangle)))
+            .unwrap();
+        let webp = encode_webp(&resized, &ProcessingOptions::default()).unwrap();
+        let decoded = decode_bytes(&webp, InputFormat::WebP).unwrap().unwrap();
+
+        assert_eq!(decoded.loop_count, 2);
+        let delays: Vec<u32> = decoded.frames.iter().map(|f| f.delay_ms).collect();
+        assert_eq!(delays, [100, 250]);
+        assert_eq!(decoded.frames[1].image.width(), 4);
+        assert!(decoded.frames[1].image.to_rgba8().get_pixel(1, 1)[2] > 200);
+    }
+
+    #[test]
+    fn test_png_round_trip_keeps_frames_and_loops() {
+        let png = encode_png(&animation(3), &ImageMetadata::default()).unwrap();
+        let decoded = decode_bytes(&png, InputFormat::Png).unwrap().unwrap();
+
+        assert_eq!(decoded.loop_count, 3);
+        let delays: Vec<u32> = decoded.frames.iter().map(|f| f.delay_ms).collect();
+        assert_eq!(delays, [100, 250]);
+        assert_eq!(
+            decoded.frames[1].image.to_rgba8().get_pixel(0, 0).0,
+            [0, 0, 255, 255]
+        );
+        assert_eq!(png_delay(70_000), (70, 1));
+    }
+
+    #[test]
+    fn test_gif_loop_count_ignores_text_in_comments() {
+        let mut gif = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut gif, 1, 1, &[0, 0, 0, 255, 255, 255]).unwrap();
+            encoder
+                .write_raw_extension(
+                    gif::Extension::Comment.into(),
+                    &[b"NETSCAPE2.0\x03\x01\0\0"],
+                )
+                .unwrap();
+            for _ in 0..2 {
+                encoder
+                    .write_frame(&gif::Frame {
+                        width: 1,
+                        height: 1,
+                        buffer: vec![1].into(),
+                        ..gif::Frame::default()
+                    })
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(gif_loop_count(&gif), 1);
+    }
+
+    #[test]
+    fn test_still_images_are_not_animations() {
+        let still = Animation {
+            frames: animation(1).frames.split_off(1),
+            loop_count: 1,
+        };
+        let gif = encode_gif(&still, &ProcessingOptions::default()).unwrap();
+        assert!(decode_bytes(&gif, InputFormat::Gif).unwrap().is_none());
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgba8(2, 2)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        assert!(decode_bytes(&png, InputFormat::Png).unwrap().is_none());
+    }
+}
@@ -0,0 +1,200 @@
+use image::{DynamicImage, ImageBuffer, Pixel};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+use serde::{Deserialize, Serialize};
+
+use super::processor::ProcessError;
+
+/// What to do with the color profile embedded in the source image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorManagement {
+    /// Keep pixel values as they are and embed the source profile
+    #[default]
+    Preserve,
+    /// Convert pixels to sRGB and embed a compact sRGB profile
+    ConvertToSrgb,
+    /// Convert pixels to sRGB and embed no profile (viewers assume sRGB)
+    ConvertToSrgbUntagged,
+}
+
+impl ColorManagement {
+    /// Whether pixel data is converted to sRGB
+    pub fn converts(&self) -> bool {
+        *self != ColorManagement::Preserve
+    }
+}
+
+/// Encode a minimal sRGB profile to embed after conversion
+pub fn srgb_profile() -> Result<Vec<u8>, ProcessError> {
+    ColorProfile::new_srgb()
+        .encode()
+        .map_err(|e| ProcessError::ProcessingFailed(format!("sRGB profile: {}", e)))
+}
+
+/// Transform pixel data from the given ICC profile into sRGB
+///
+/// RGB and grayscale source profiles are supported. The bit depth of the image
+/// is kept; grayscale images become RGB since sRGB is an RGB color space.
+pub fn convert_to_srgb(
+    img: &DynamicImage,
+    icc_profile: &[u8],
+) -> Result<DynamicImage, ProcessError> {
+    let source = ColorProfile::new_from_slice(icc_profile).map_err(cms_error)?;
+    let srgb = ColorProfile::new_srgb();
+
+    let gray = match source.color_space {
+        DataColorSpace::Rgb => false,
+        DataColorSpace::Gray => true,
+        other => {
+            return Err(ProcessError::ProcessingFailed(format!(
+                "Cannot convert from a {:?} color profile",
+                other
+            )))
+        }
+    };
+    let has_alpha = img.color().has_alpha();
+    let (src_layout, dst_layout) = match (gray, has_alpha) {
+        (false, false) => (Layout::Rgb, Layout::Rgb),
+        (false, true) => (Layout::Rgba, Layout::Rgba),
+        (true, false) => (Layout::Gray, Layout::Rgb),
+        (true, true) => (Layout::GrayAlpha, Layout::Rgba),
+    };
+    let options = TransformOptions::default();
+
+    let converted = match img {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            let transform = source
+                .create_transform_f32(src_layout, &srgb, dst_layout, options)
+                .map_err(cms_error)?;
+            let src: Vec<f32> = match (gray, has_alpha) {
+                (false, false) => img.to_rgb32f().into_raw(),
+                (false, true) => img.to_rgba32f().into_raw(),
+                (true, false) => img.to_rgb32f().pixels().map(|p| p.to_luma()[0]).collect(),
+                (true, true) => img
+                    .to_rgba32f()
+                    .pixels()
+                    .flat_map(|p| p.to_luma_alpha().0)
+                    .collect(),
+            };
+            let mut dst = vec![0.0; pixel_count(img) * dst_layout.channels()];
+            transform.transform(&src, &mut dst).map_err(cms_error)?;
+            rebuild(
+                img,
+                dst,
+                has_alpha,
+                DynamicImage::ImageRgb32F,
+                DynamicImage::ImageRgba32F,
+            )
+        }
+        DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgb16(_)
+        | DynamicImage::ImageRgba16(_) => {
+            let transform = source
+                .create_transform_16bit(src_layout, &srgb, dst_layout, options)
+                .map_err(cms_error)?;
+            let src: Vec<u16> = match (gray, has_alpha) {
+                (false, false) => img.to_rgb16().into_raw(),
+                (false, true) => img.to_rgba16().into_raw(),
+                (true, false) => img.to_luma16().into_raw(),
+                (true, true) => img.to_luma_alpha16().into_raw(),
+            };
+            let mut dst = vec![0; pixel_count(img) * dst_layout.channels()];
+            transform.transform(&src, &mut dst).map_err(cms_error)?;
+            rebuild(
+                img,
+                dst,
+                has_alpha,
+                DynamicImage::ImageRgb16,
+                DynamicImage::ImageRgba16,
+            )
+        }
+        _ => {
+            let transform = source
+                .create_transform_8bit(src_layout, &srgb, dst_layout, options)
+                .map_err(cms_error)?;
+            let src: Vec<u8> = match (gray, has_alpha) {
+                (false, false) => img.to_rgb8().into_raw(),
+                (false, true) => img.to_rgba8().into_raw(),
+                (true, false) => img.to_luma8().into_raw(),
+                (true, true) => img.to_luma_alpha8().into_raw(),
+            };
+            let mut dst = vec![0; pixel_count(img) * dst_layout.channels()];
+            transform.transform(&src, &mut dst).map_err(cms_error)?;
+            rebuild(
+                img,
+                dst,
+                has_alpha,
+                DynamicImage::ImageRgb8,
+                DynamicImage::ImageRgba8,
+            )
+        }
+    };
+
+    Ok(converted)
+}
+
+fn pixel_count(img: &DynamicImage) -> usize {
+    img.width() as usize * img.height() as usize
+}
+
+/// Wrap transformed samples back into an RGB or RGBA image of the same size
+fn rebuild<T, Rgb, Rgba>(
+    img: &DynamicImage,
+    data: Vec<T>,
+    has_alpha: bool,
+    rgb: fn(ImageBuffer<Rgb, Vec<T>>) -> DynamicImage,
+    rgba: fn(ImageBuffer<Rgba, Vec<T>>) -> DynamicImage,
+) -> DynamicImage
+where
+    Rgb: Pixel<Subpixel = T>,
+    Rgba: Pixel<Subpixel = T>,
+{
+    let (width, height) = (img.width(), img.height());
+    // The buffer length always matches the layout, so construction cannot fail
+    if has_alpha {
+        rgba(ImageBuffer::from_raw(width, height, data).expect("RGBA buffer size"))
+    } else {
+        rgb(ImageBuffer::from_raw(width, height, data).expect("RGB buffer size"))
+    }
+}
+
+fn cms_error(e: moxcms::CmsError) -> ProcessError {
+    ProcessError::ProcessingFailed(format!("Color conversion failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba};
+
+    fn display_p3() -> Vec<u8> {
+        ColorProfile::new_display_p3().encode().unwrap()
+    }
+
+    #[test]
+    fn test_convert_to_srgb_from_srgb_is_near_identity() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([200, 100, 50])));
+        let converted = convert_to_srgb(&img, &srgb_profile().unwrap()).unwrap();
+        let pixel = converted.as_rgb8().unwrap().get_pixel(0, 0);
+        for (a, b) in pixel.0.iter().zip([200u8, 100, 50]) {
+            assert!(a.abs_diff(b) <= 1, "{:?}", pixel);
+        }
+    }
+
+    #[test]
+    fn test_convert_to_srgb_keeps_bit_depth_and_alpha() {
+        let source = Rgba([65535u16, 32768, 32768, 1234]);
+        let img = DynamicImage::ImageRgba16(image::ImageBuffer::from_pixel(3, 1, source));
+        let converted = convert_to_srgb(&img, &display_p3()).unwrap();
+        let pixel = converted.as_rgba16().unwrap().get_pixel(0, 0);
+        assert_eq!(pixel[3], 1234);
+        // A saturated P3 color is more saturated once expressed in sRGB
+        assert!(pixel[1] < source[1] && pixel[2] < source[2], "{:?}", pixel);
+
+        let img =
+            DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, Rgba([0, 255, 0, 255])));
+        let converted = convert_to_srgb(&img, &display_p3()).unwrap();
+        assert!(converted.as_rgba8().is_some());
+    }
+}
@@ -0,0 +1,99 @@
+use image::{DynamicImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, Rgba};
+
+/// 4x4 ordered-dither (Bayer) threshold map
+const BAYER_4X4: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Whether the image stores more than 8 bits per channel
+pub fn is_high_bit_depth(img: &DynamicImage) -> bool {
+    let color = img.color();
+    color.bytes_per_pixel() > color.channel_count()
+}
+
+/// Reduce a 16-bit or float image to 8 bits per channel with ordered dithering
+///
+/// Dithering avoids the banding that plain rounding produces in smooth gradients.
+/// Alpha is rounded, not dithered. 8-bit images are returned unchanged.
+pub fn to_8bit_dithered(img: DynamicImage) -> DynamicImage {
+    if !is_high_bit_depth(&img) {
+        return img;
+    }
+
+    let color = img.color();
+    match (color.has_color(), color.has_alpha()) {
+        (false, false) => DynamicImage::ImageLuma8(dither::<Luma<u16>, Luma<u8>>(&img.to_luma16())),
+        (false, true) => {
+            DynamicImage::ImageLumaA8(dither::<LumaA<u16>, LumaA<u8>>(&img.to_luma_alpha16()))
+        }
+        (true, false) => DynamicImage::ImageRgb8(dither::<Rgb<u16>, Rgb<u8>>(&img.to_rgb16())),
+        (true, true) => DynamicImage::ImageRgba8(dither::<Rgba<u16>, Rgba<u8>>(&img.to_rgba16())),
+    }
+}
+
+/// Ordered-dither offset of a pixel, spread evenly over (-0.5, 0.5)
+pub fn bayer_offset(x: u32, y: u32) -> f32 {
+    (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0 - 0.5
+}
+
+fn dither<P, Q>(buf: &ImageBuffer<P, Vec<u16>>) -> ImageBuffer<Q, Vec<u8>>
+where
+    P: Pixel<Subpixel = u16>,
+    Q: Pixel<Subpixel = u8>,
+{
+    let (width, height) = buf.dimensions();
+    let channels = P::CHANNEL_COUNT as usize;
+    let alpha_channel = P::HAS_ALPHA.then_some(channels - 1);
+
+    let data = buf
+        .as_raw()
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| {
+            let value = u32::from(value);
+            if Some(i % channels) == alpha_channel {
+                return ((value + 128) / 257) as u8;
+            }
+            let pixel = (i / channels) as u32;
+            let (x, y) = (pixel % width, pixel / width);
+            // Spread the 16 thresholds evenly over one 8-bit step (257 in 16-bit units)
+            let threshold = (2 * BAYER_4X4[(y % 4) as usize][(x % 4) as usize] + 1) * 257 / 32;
+            ((value + threshold) / 257).min(255) as u8
+        })
+        .collect();
+
+    // The sample count is unchanged, so the buffer always fits
+    ImageBuffer::from_raw(width, height, data).expect("dithered buffer size")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_8bit_dithered_preserves_mean_and_alpha() {
+        // Halfway between two 8-bit levels
+        let level = 100 * 257 + 128;
+        let img = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(
+            8,
+            8,
+            Rgba([level, 0, 65535, 32896]),
+        ));
+
+        let out = to_8bit_dithered(img);
+        let out = out.as_rgba8().unwrap();
+        let reds: Vec<u8> = out.pixels().map(|p| p[0]).collect();
+        assert!(reds.contains(&100) && reds.contains(&101));
+        let mean = reds.iter().map(|&r| f64::from(r)).sum::<f64>() / reds.len() as f64;
+        assert!((mean - 100.5).abs() < 0.1, "{}", mean);
+        assert!(out
+            .pixels()
+            .all(|p| p[1] == 0 && p[2] == 255 && p[3] == 128));
+    }
+
+    #[test]
+    fn test_to_8bit_dithered_keeps_8bit_images() {
+        let img = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(2, 2, Luma([7])));
+        assert!(!is_high_bit_depth(&img));
+        assert_eq!(to_8bit_dithered(img.clone()), img);
+        assert!(is_high_bit_depth(&DynamicImage::new_rgb32f(1, 1)));
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 /// Supported input formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -10,6 +11,18 @@ pub enum InputFormat {
     Bmp,
     Tiff,
     WebP,
+    Avif,
+    Heic,
+    Jxl,
+    Qoi,
+    Tga,
+    Pnm,
+    Ico,
+    Dds,
+    Hdr,
+    Exr,
+    Svg,
+    Raw,
 }
 
 /// Supported output formats
@@ -22,29 +35,88 @@ pub enum OutputFormat {
     Bmp,
     Tiff,
     WebP,
+    Avif,
+    Jxl,
+    Ico,
+    Icns,
 }
 
 impl InputFormat {
+    /// Every file extension that maps to an input format
+    pub const EXTENSIONS: [&'static str; 30] = [
+        "jpg", "jpeg", "png", "apng", "gif", "bmp", "tiff", "tif", "webp", "avif", "heic", "heif",
+        "jxl", "qoi", "tga", "pnm", "pbm", "pgm", "ppm", "pam", "ico", "dds", "hdr", "exr", "svg",
+        "svgz", "dng", "cr2", "nef", "arw",
+    ];
+
     /// Get format from file extension
     pub fn from_extension(ext: &str) -> Option<Self> {
         match ext.to_lowercase().as_str() {
             "jpg" | "jpeg" => Some(Self::Jpeg),
-            "png" => Some(Self::Png),
+            "png" | "apng" => Some(Self::Png),
             "gif" => Some(Self::Gif),
             "bmp" => Some(Self::Bmp),
             "tiff" | "tif" => Some(Self::Tiff),
             "webp" => Some(Self::WebP),
+            "avif" => Some(Self::Avif),
+            "heic" | "heif" => Some(Self::Heic),
+            "jxl" => Some(Self::Jxl),
+            "qoi" => Some(Self::Qoi),
+            "tga" => Some(Self::Tga),
+            "pnm" | "pbm" | "pgm" | "ppm" | "pam" => Some(Self::Pnm),
+            "ico" => Some(Self::Ico),
+            "dds" => Some(Self::Dds),
+            "hdr" => Some(Self::Hdr),
+            "exr" => Some(Self::Exr),
+            "svg" | "svgz" => Some(Self::Svg),
+            "dng" | "cr2" | "nef" | "arw" => Some(Self::Raw),
             _ => None,
         }
     }
 
-    /// Check if the extension is supported
+    /// Get format from a file path's extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| Self::from_extension(&ext.to_string_lossy()))
+    }
+
+    /// Check if the extension is supported and this build can decode it
     pub fn is_supported(ext: &str) -> bool {
-        Self::from_extension(ext).is_some()
+        Self::from_extension(ext).is_some_and(|format| format.is_available())
+    }
+
+    /// Check if this build can decode the format (HEIF/AVIF and JPEG XL are optional features)
+    pub fn is_available(&self) -> bool {
+        let needs_heif = matches!(self, Self::Heic | Self::Avif);
+        (!needs_heif || cfg!(feature = "heif")) && (*self != Self::Jxl || cfg!(feature = "jxl"))
+    }
+
+    /// Whether pixels are stored as scene-linear light (float, unbounded)
+    pub fn is_scene_linear(&self) -> bool {
+        matches!(self, Self::Hdr | Self::Exr)
     }
 }
 
 impl OutputFormat {
+    /// All output formats, in the order they are offered
+    pub const ALL: [Self; 10] = [
+        Self::WebP,
+        Self::Avif,
+        Self::Jxl,
+        Self::Jpeg,
+        Self::Png,
+        Self::Gif,
+        Self::Bmp,
+        Self::Tiff,
+        Self::Ico,
+        Self::Icns,
+    ];
+
+    /// Check if this build can write the format (JPEG XL needs the `jxl` feature)
+    pub fn is_available(&self) -> bool {
+        *self != Self::Jxl || cfg!(feature = "jxl")
+    }
+
     /// Get file extension for this format
     pub fn extension(&self) -> &'static str {
         match self {
@@ -54,6 +126,10 @@ impl OutputFormat {
             Self::Bmp => "bmp",
             Self::Tiff => "tiff",
             Self::WebP => "webp",
+            Self::Avif => "avif",
+            Self::Jxl => "jxl",
+            Self::Ico => "ico",
+            Self::Icns => "icns",
         }
     }
 
@@ -66,8 +142,27 @@ impl OutputFormat {
             Self::Bmp => "image/bmp",
             Self::Tiff => "image/tiff",
             Self::WebP => "image/webp",
+            Self::Avif => "image/avif",
+            Self::Jxl => "image/jxl",
+            Self::Ico => "image/vnd.microsoft.icon",
+            Self::Icns => "image/icns",
         }
     }
+
+    /// Check if this format can hold several animation frames
+    pub fn supports_animation(&self) -> bool {
+        matches!(self, Self::Gif | Self::WebP | Self::Png)
+    }
+
+    /// Check if this format can hold several pages in one file
+    pub fn supports_pages(&self) -> bool {
+        matches!(self, Self::Tiff)
+    }
+
+    /// Check if this format can carry EXIF metadata
+    pub fn supports_metadata(&self) -> bool {
+        matches!(self, Self::Jpeg | Self::Png | Self::Tiff | Self::WebP)
+    }
 }
 
 #[cfg(test)]
@@ -80,12 +175,47 @@ mod tests {
         assert_eq!(InputFormat::from_extension("JPEG"), Some(InputFormat::Jpeg));
         assert_eq!(InputFormat::from_extension("png"), Some(InputFormat::Png));
         assert_eq!(InputFormat::from_extension("webp"), Some(InputFormat::WebP));
+        assert_eq!(InputFormat::from_extension("HEIC"), Some(InputFormat::Heic));
+        assert_eq!(InputFormat::from_extension("avif"), Some(InputFormat::Avif));
+        assert_eq!(InputFormat::from_extension("jxl"), Some(InputFormat::Jxl));
+        assert_eq!(InputFormat::from_extension("TGA"), Some(InputFormat::Tga));
+        assert_eq!(InputFormat::from_extension("ppm"), Some(InputFormat::Pnm));
+        assert_eq!(InputFormat::from_extension("exr"), Some(InputFormat::Exr));
+        assert_eq!(InputFormat::from_extension("svgz"), Some(InputFormat::Svg));
+        assert_eq!(InputFormat::from_extension("NEF"), Some(InputFormat::Raw));
         assert_eq!(InputFormat::from_extension("unknown"), None);
     }
 
+    #[test]
+    fn test_input_extensions_follow_features() {
+        assert!(InputFormat::EXTENSIONS
+            .iter()
+            .all(|ext| InputFormat::from_extension(ext).is_some()));
+        assert!(InputFormat::is_supported("png"));
+        assert_eq!(InputFormat::is_supported("heic"), cfg!(feature = "heif"));
+        assert_eq!(InputFormat::is_supported("jxl"), cfg!(feature = "jxl"));
+    }
+
     #[test]
     fn test_output_format_extension() {
         assert_eq!(OutputFormat::Jpeg.extension(), "jpg");
         assert_eq!(OutputFormat::WebP.extension(), "webp");
+        assert_eq!(OutputFormat::Avif.extension(), "avif");
+        assert_eq!(OutputFormat::Avif.mime_type(), "image/avif");
+        assert_eq!(OutputFormat::Jxl.extension(), "jxl");
+    }
+
+    #[test]
+    fn test_output_format_is_available() {
+        assert!(OutputFormat::WebP.is_available());
+        assert_eq!(OutputFormat::Jxl.is_available(), cfg!(feature = "jxl"));
+    }
+
+    #[test]
+    fn test_output_format_supports_metadata() {
+        assert!(OutputFormat::Jpeg.supports_metadata());
+        assert!(OutputFormat::WebP.supports_metadata());
+        assert!(!OutputFormat::Gif.supports_metadata());
+        assert!(!OutputFormat::Bmp.supports_metadata());
     }
 }
@@ -0,0 +1,123 @@
+use image::DynamicImage;
+use std::path::Path;
+
+use super::formats::InputFormat;
+use super::processor::ProcessError;
+
+/// Check if a file is a HEIF container (HEIC or AVIF) that needs libheif to decode
+pub fn is_heif(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| InputFormat::from_extension(&ext.to_string_lossy()))
+        .is_some_and(|format| matches!(format, InputFormat::Heic | InputFormat::Avif))
+}
+
+/// Decode the primary image of a HEIF container
+///
+/// libheif applies the container's rotation and mirroring, so the result is
+/// already upright and the EXIF Orientation tag must not be applied again.
+#[cfg(feature = "heif")]
+pub fn decode(path: &Path) -> Result<DynamicImage, ProcessError> {
+    use image::ImageBuffer;
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let lib_heif = LibHeif::new();
+    let context = HeifContext::read_from_file(&path.to_string_lossy())
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let handle = context
+        .primary_image_handle()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    let has_alpha = handle.has_alpha_channel();
+    let high_bit_depth = handle.luma_bits_per_pixel() > 8;
+    let chroma = match (high_bit_depth, has_alpha) {
+        (false, false) => RgbChroma::Rgb,
+        (false, true) => RgbChroma::Rgba,
+        (true, false) => RgbChroma::HdrRgbLe,
+        (true, true) => RgbChroma::HdrRgbaLe,
+    };
+
+    let image = lib_heif
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let planes = image.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| ProcessError::ReadError("HEIF image has no RGB plane".to_string()))?;
+
+    let (width, height) = (plane.width, plane.height);
+    let channels = if has_alpha { 4 } else { 3 };
+    let row_bytes = width as usize * channels * if high_bit_depth { 2 } else { 1 };
+    // Rows are padded to the stride
+    let rows = plane.data.chunks(plane.stride).map(|row| &row[..row_bytes]);
+
+    let buffer_error = || ProcessError::ReadError("Unexpected HEIF buffer size".to_string());
+
+    let img = if high_bit_depth {
+        // Samples hold `bits_per_pixel` bits (10 or 12); scale them to the full 16-bit range
+        let bits = u32::from(plane.bits_per_pixel);
+        let data: Vec<u16> = rows
+            .flat_map(|row| row.chunks_exact(2))
+            .map(|sample| {
+                let value = u32::from(u16::from_le_bytes([sample[0], sample[1]]));
+                ((value << (16 - bits)) | (value >> (2 * bits - 16))) as u16
+            })
+            .collect();
+        if has_alpha {
+            DynamicImage::ImageRgba16(
+                ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+            )
+        } else {
+            DynamicImage::ImageRgb16(
+                ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+            )
+        }
+    } else {
+        let data: Vec<u8> = rows.flatten().copied().collect();
+        if has_alpha {
+            DynamicImage::ImageRgba8(
+                ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+            )
+        } else {
+            DynamicImage::ImageRgb8(
+                ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+            )
+        }
+    };
+
+    Ok(img)
+}
+
+#[cfg(not(feature = "heif"))]
+pub fn decode(_path: &Path) -> Result<DynamicImage, ProcessError> {
+    Err(unsupported())
+}
+
+/// Read the embedded ICC profile (NCLX color descriptions are not returned)
+#[cfg(feature = "heif")]
+pub fn icc_profile(path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    use libheif_rs::{color_profile_types, ColorProfile, HeifContext};
+
+    let context = HeifContext::read_from_file(&path.to_string_lossy())
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let handle = context
+        .primary_image_handle()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    Ok(handle
+        .color_profile_raw()
+        .filter(|profile| profile.profile_type() != color_profile_types::NCLX)
+        .map(|profile| profile.data))
+}
+
+/// Without libheif the file is treated as having no profile, so metadata can still be read
+#[cfg(not(feature = "heif"))]
+pub fn icc_profile(_path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    Ok(None)
+}
+
+#[cfg(not(feature = "heif"))]
+fn unsupported() -> ProcessError {
+    ProcessError::UnsupportedFormat(
+        "HEIC/AVIF decoding is not included in this build (enable the `heif` feature)".to_string(),
+    )
+}
@@ -0,0 +1,160 @@
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::png::PngEncoder;
+use image::{imageops, DynamicImage, ExtendedColorType, ImageEncoder, RgbaImage};
+
+use super::processor::{ImageProcessor, ProcessError};
+
+/// Icon sizes generated when none are configured
+pub const DEFAULT_ICON_SIZES: [u32; 8] = [16, 32, 48, 64, 128, 256, 512, 1024];
+
+/// Largest size an ICO entry can describe
+const ICO_MAX_SIZE: u32 = 256;
+
+/// ICNS element types holding PNG data, by pixel size
+///
+/// Retina variants (`ic11`..`ic14`) share sizes with the 1x types, so each size
+/// is stored once under its 1x type. 48px has no PNG type and is not written.
+const ICNS_TYPES: [(u32, &[u8; 4]); 7] = [
+    (16, b"icp4"),
+    (32, b"icp5"),
+    (64, b"icp6"),
+    (128, b"ic07"),
+    (256, b"ic08"),
+    (512, b"ic09"),
+    (1024, b"ic10"),
+];
+
+/// Encode a Windows icon with one PNG entry per size
+///
+/// Sizes above 256 are skipped, as ICO cannot describe them.
+pub fn encode_ico(img: &DynamicImage, sizes: &[u32]) -> Result<Vec<u8>, ProcessError> {
+    let encoded = sorted_sizes(sizes, |size| size <= ICO_MAX_SIZE)?
+        .into_iter()
+        .map(|size| {
+            let icon = square_icon(img, size);
+            IcoFrame::as_png(icon.as_raw(), size, size, ExtendedColorType::Rgba8)
+                .map_err(|e| ProcessError::WriteError(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = Vec::new();
+    IcoEncoder::new(&mut out)
+        .encode_images(&encoded)
+        .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+    Ok(out)
+}
+
+/// Encode a macOS icon with one PNG element per size
+///
+/// Sizes without a PNG element type (such as 48) are skipped.
+pub fn encode_icns(img: &DynamicImage, sizes: &[u32]) -> Result<Vec<u8>, ProcessError> {
+    let mut elements = Vec::new();
+    for size in sorted_sizes(sizes, |size| icns_type(size).is_some())? {
+        let icon = square_icon(img, size);
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(icon.as_raw(), size, size, ExtendedColorType::Rgba8)
+            .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+        // Element header: type and length including the header itself
+        let icns_type = icns_type(size).expect("filtered to known sizes");
+        elements.extend_from_slice(icns_type);
+        elements.extend_from_slice(&(png.len() as u32 + 8).to_be_bytes());
+        elements.extend_from_slice(&png);
+    }
+
+    let mut out = Vec::with_capacity(elements.len() + 8);
+    out.extend_from_slice(b"icns");
+    out.extend_from_slice(&(elements.len() as u32 + 8).to_be_bytes());
+    out.extend_from_slice(&elements);
+
+    Ok(out)
+}
+
+fn icns_type(size: u32) -> Option<&'static [u8; 4]> {
+    ICNS_TYPES
+        .iter()
+        .find(|(icns_size, _)| *icns_size == size)
+        .map(|(_, icns_type)| *icns_type)
+}
+
+/// Sizes the container can hold, smallest first, without duplicates
+fn sorted_sizes(sizes: &[u32], fits: impl Fn(u32) -> bool) -> Result<Vec<u32>, ProcessError> {
+    let sizes = if sizes.is_empty() {
+        &DEFAULT_ICON_SIZES[..]
+    } else {
+        sizes
+    };
+    let mut sizes: Vec<u32> = sizes
+        .iter()
+        .copied()
+        .filter(|&size| size > 0 && fits(size))
+        .collect();
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    if sizes.is_empty() {
+        return Err(ProcessError::ProcessingFailed(
+            "None of the icon sizes can be stored in this format".to_string(),
+        ));
+    }
+    Ok(sizes)
+}
+
+/// Scale the image to fit a `size` square and center it on a transparent canvas
+fn square_icon(img: &DynamicImage, size: u32) -> RgbaImage {
+    let (width, height) = (u64::from(img.width()), u64::from(img.height()));
+    let longest = width.max(height).max(1);
+    let scaled_width = (width * u64::from(size) / longest).max(1) as u32;
+    let scaled_height = (height * u64::from(size) / longest).max(1) as u32;
+
+    let scaled = ImageProcessor::resize(img.clone(), Some(scaled_width), Some(scaled_height));
+
+    let mut canvas = RgbaImage::new(size, size);
+    imageops::overlay(
+        &mut canvas,
+        &scaled.to_rgba8(),
+        i64::from((size - scaled_width) / 2),
+        i64::from((size - scaled_height) / 2),
+    );
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_square_icon_pads_to_square() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 20, Rgba([255, 0, 0, 255])));
+        let icon = square_icon(&img, 16);
+
+        assert_eq!(icon.dimensions(), (16, 16));
+        assert_eq!(icon.get_pixel(8, 8)[3], 255);
+        assert_eq!(icon.get_pixel(8, 0)[3], 0);
+    }
+
+    #[test]
+    fn test_encode_icns_and_ico() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 64, Rgba([0, 0, 255, 255])));
+
+        let icns = encode_icns(&img, &[32, 16, 48, 32]).unwrap();
+        assert_eq!(&icns[0..4], b"icns");
+        assert_eq!(
+            u32::from_be_bytes(icns[4..8].try_into().unwrap()) as usize,
+            icns.len()
+        );
+        // 48 is skipped and the duplicate 32 is written once
+        assert_eq!(&icns[8..12], b"icp4");
+        let first = u32::from_be_bytes(icns[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&icns[8 + first..12 + first], b"icp5");
+
+        let ico = encode_ico(&img, &[16, 256, 1024]).unwrap();
+        let ico = image::load_from_memory(&ico).unwrap();
+        // The decoder picks the largest entry
+        assert_eq!(ico.width(), 256);
+        assert!(encode_ico(&img, &[512]).is_err());
+    }
+}
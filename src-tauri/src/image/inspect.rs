@@ -0,0 +1,457 @@
+use exif::{Context, Field, In, Reader, Tag, Value};
+use image::{ImageDecoder, ImageFormat, ImageReader};
+use moxcms::{ColorProfile, ProfileText};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::heif;
+use super::jxl;
+use super::metadata::{self, MetadataCategory};
+use super::processor::ProcessError;
+use super::svg;
+
+const XMP_TAG: u16 = 700;
+const IPTC_TAG: u16 = 33723;
+const PHOTOSHOP_TAG: u16 = 34377;
+const PHOTOSHOP_IRB: &[u8] = b"Photoshop 3.0\0";
+const IRB_IPTC: u16 = 0x0404;
+
+/// Longer binary values are summarized instead of dumped
+const MAX_BINARY_DISPLAY: usize = 64;
+
+/// Metadata stored in an image file, for display
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataReport {
+    /// EXIF fields of the primary image
+    pub exif: Vec<ExifEntry>,
+    /// Raw XMP packet
+    pub xmp: Option<String>,
+    /// IPTC-IIM datasets
+    pub iptc: Vec<IptcEntry>,
+    /// Description of the embedded ICC profile
+    pub icc_description: Option<String>,
+    /// Resolution in dots per inch
+    pub dpi: Option<Dpi>,
+}
+
+/// A single EXIF field with a human-readable value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExifEntry {
+    /// IFD the field lives in (Image, Exif, GPS, Interop)
+    pub ifd: String,
+    pub tag: String,
+    pub value: String,
+    /// Category used by metadata policies
+    pub category: MetadataCategory,
+}
+
+/// A single IPTC-IIM dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IptcEntry {
+    /// Dataset name, or `record:dataset` when unknown
+    pub name: String,
+    pub value: String,
+}
+
+/// Horizontal and vertical resolution
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Dpi {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl MetadataReport {
+    /// Read all metadata of an image file
+    ///
+    /// Only the container headers are parsed; pixels are not decoded.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ProcessError> {
+        let path = path.as_ref();
+        let format = ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| ProcessError::ReadError(e.to_string()))?
+            .format();
+
+        let (exif, exif_dpi) = read_exif(path)?;
+
+        let (xmp, iptc) = read_xmp_iptc(path)?;
+
+        let dpi = match format {
+            _ if exif_dpi.is_some() => exif_dpi,
+            Some(ImageFormat::Jpeg) => jfif_dpi(path)?,
+            Some(ImageFormat::Png) => png_dpi(path)?,
+            _ => None,
+        };
+
+        Ok(Self {
+            exif,
+            xmp: xmp.map(|packet| String::from_utf8_lossy(&packet).into_owned()),
+            iptc: iptc.as_deref().map(parse_iim).unwrap_or_default(),
+            icc_description: metadata::read_icc_profile(path)?
+                .and_then(|profile| icc_description(&profile)),
+            dpi,
+        })
+    }
+}
+
+/// Raw XMP packet and IPTC-IIM block of a file
+type XmpIptc = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Read the raw XMP packet and IPTC-IIM block (headers only)
+pub(super) fn read_xmp_iptc(path: &Path) -> Result<XmpIptc, ProcessError> {
+    // Not exposed by image's decoders, which cannot open these
+    if heif::is_heif(path) || jxl::is_jxl(path) || svg::is_svg(path) {
+        return Ok((None, None));
+    }
+    let format = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?
+        .format();
+
+    Ok(match format {
+        Some(ImageFormat::Tiff) => (
+            metadata::read_tiff_tag(path, tiff::tags::Tag::from_u16_exhaustive(XMP_TAG))?,
+            tiff_iptc(path)?,
+        ),
+        Some(ImageFormat::Jpeg) => (read_xmp(path)?, jpeg_iptc(path)?),
+        _ => (read_xmp(path)?, None),
+    })
+}
+
+/// Read EXIF entries, plus the resolution they declare
+fn read_exif(path: &Path) -> Result<(Vec<ExifEntry>, Option<Dpi>), ProcessError> {
+    let file = File::open(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    let exif = match Reader::new().read_from_container(&mut BufReader::new(file)) {
+        Ok(exif) => exif,
+        Err(exif::Error::NotFound(_)) | Err(exif::Error::InvalidFormat(_)) => {
+            return Ok((Vec::new(), None))
+        }
+        Err(e) => return Err(ProcessError::ReadError(e.to_string())),
+    };
+
+    let entries = exif
+        .fields()
+        .filter(|f| f.ifd_num == In::PRIMARY)
+        .map(|field| ExifEntry {
+            ifd: ifd_name(field.tag.context()).to_string(),
+            tag: field.tag.to_string(),
+            value: display_value(field, &exif),
+            category: MetadataCategory::of(field),
+        })
+        .collect();
+
+    let resolution = |tag| {
+        exif.get_field(tag, In::PRIMARY)
+            .and_then(|f| match &f.value {
+                Value::Rational(v) if !v.is_empty() && v[0].denom != 0 => Some(v[0].to_f64()),
+                _ => None,
+            })
+    };
+    // ResolutionUnit: 2 = inch (default), 3 = centimeter
+    let per_inch = match exif
+        .get_field(Tag::ResolutionUnit, In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+    {
+        Some(3) => 2.54,
+        Some(1) => return Ok((entries, None)),
+        _ => 1.0,
+    };
+    let dpi = resolution(Tag::XResolution)
+        .zip(resolution(Tag::YResolution))
+        .map(|(x, y)| Dpi {
+            x: x * per_inch,
+            y: y * per_inch,
+        });
+
+    Ok((entries, dpi))
+}
+
+fn ifd_name(context: Context) -> &'static str {
+    match context {
+        Context::Tiff => "Image",
+        Context::Exif => "Exif",
+        Context::Gps => "GPS",
+        Context::Interop => "Interop",
+        _ => "Other",
+    }
+}
+
+fn display_value(field: &Field, exif: &exif::Exif) -> String {
+    match &field.value {
+        Value::Undefined(bytes, _) if bytes.len() > MAX_BINARY_DISPLAY => {
+            format!("({} bytes)", bytes.len())
+        }
+        // display_value() quotes strings, which reads oddly in the UI
+        Value::Ascii(strings) => strings
+            .iter()
+            .map(|s| {
+                String::from_utf8_lossy(s)
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => field.display_value().with_unit(exif).to_string(),
+    }
+}
+
+/// Read the XMP packet through the image decoder (headers only)
+fn read_xmp(path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    let mut decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?
+        .into_decoder()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    decoder
+        .xmp_metadata()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))
+}
+
+/// Text of the profile description tag
+fn icc_description(profile: &[u8]) -> Option<String> {
+    let text = match ColorProfile::new_from_slice(profile).ok()?.description? {
+        ProfileText::PlainString(text) => text,
+        ProfileText::Localizable(strings) => strings.into_iter().next()?.value,
+        ProfileText::Description(description) => description.ascii_string,
+    };
+    let text = text.trim_end_matches('\0').trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Collect the APPn segments of a JPEG file, stopping at the first scan
+fn jpeg_app_segments(path: &Path) -> Result<Vec<(u8, Vec<u8>)>, ProcessError> {
+    let file = File::open(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let mut reader = BufReader::new(file);
+    let read_err = |e: std::io::Error| ProcessError::ReadError(e.to_string());
+
+    let mut marker = [0u8; 2];
+    reader.read_exact(&mut marker).map_err(read_err)?;
+    if marker != [0xff, 0xd8] {
+        return Ok(Vec::new());
+    }
+
+    let mut segments = Vec::new();
+    loop {
+        if reader.read_exact(&mut marker).is_err() || marker[0] != 0xff {
+            break;
+        }
+        // Start of scan: only entropy-coded data follows
+        if marker[1] == 0xda {
+            break;
+        }
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length).map_err(read_err)?;
+        let length = u16::from_be_bytes(length).saturating_sub(2) as usize;
+
+        if (0xe0..=0xef).contains(&marker[1]) {
+            let mut data = vec![0; length];
+            reader.read_exact(&mut data).map_err(read_err)?;
+            segments.push((marker[1], data));
+        } else {
+            reader
+                .seek(SeekFrom::Current(length as i64))
+                .map_err(read_err)?;
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Extract IPTC-IIM data from the Photoshop resources in JPEG APP13 segments
+fn jpeg_iptc(path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    let iptc = jpeg_app_segments(path)?
+        .into_iter()
+        .filter(|(marker, data)| *marker == 0xed && data.starts_with(PHOTOSHOP_IRB))
+        .find_map(|(_, data)| photoshop_resource(&data[PHOTOSHOP_IRB.len()..], IRB_IPTC));
+
+    Ok(iptc)
+}
+
+/// Read IPTC-IIM data from its own TIFF tag, or from the Photoshop resources tag
+fn tiff_iptc(path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    let tag = |number| metadata::read_tiff_tag(path, tiff::tags::Tag::from_u16_exhaustive(number));
+    if let Some(iptc) = tag(IPTC_TAG)? {
+        return Ok(Some(iptc));
+    }
+    Ok(tag(PHOTOSHOP_TAG)?.and_then(|resources| photoshop_resource(&resources, IRB_IPTC)))
+}
+
+/// Find a resource in a block of Photoshop image resources (`8BIM` records)
+fn photoshop_resource(mut data: &[u8], wanted: u16) -> Option<Vec<u8>> {
+    while data.len() >= 8 && &data[0..4] == b"8BIM" {
+        let id = u16::from_be_bytes([data[4], data[5]]);
+        // Pascal string name, padded to an even length including its length byte
+        let name_len = data[6] as usize;
+        let mut pos = 6 + name_len + 1;
+        pos += pos % 2;
+        let size_bytes = data.get(pos..pos + 4)?;
+        let size = u32::from_be_bytes(size_bytes.try_into().ok()?) as usize;
+        pos += 4;
+        let payload = data.get(pos..pos + size)?;
+
+        if id == wanted {
+            return Some(payload.to_vec());
+        }
+        data = data.get(pos + size + size % 2..)?;
+    }
+    None
+}
+
+/// Parse IPTC-IIM datasets of the application record
+fn parse_iim(data: &[u8]) -> Vec<IptcEntry> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+
+    while pos + 5 <= data.len() && data[pos] == 0x1c {
+        let record = data[pos + 1];
+        let dataset = data[pos + 2];
+        let size = u16::from_be_bytes([data[pos + 3], data[pos + 4]]) as usize;
+        // Extended-length datasets are not used for text fields
+        if size & 0x8000 != 0 {
+            break;
+        }
+        let Some(value) = data.get(pos + 5..pos + 5 + size) else {
+            break;
+        };
+
+        // Skip the envelope and the record version
+        if record == 2 && dataset != 0 {
+            entries.push(IptcEntry {
+                name: iim_name(dataset)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{}:{}", record, dataset)),
+                value: String::from_utf8_lossy(value).into_owned(),
+            });
+        }
+        pos += 5 + size;
+    }
+
+    entries
+}
+
+fn iim_name(dataset: u8) -> Option<&'static str> {
+    Some(match dataset {
+        5 => "ObjectName",
+        15 => "Category",
+        20 => "SupplementalCategories",
+        25 => "Keywords",
+        40 => "SpecialInstructions",
+        55 => "DateCreated",
+        60 => "TimeCreated",
+        80 => "By-line",
+        85 => "By-lineTitle",
+        90 => "City",
+        92 => "Sub-location",
+        95 => "Province-State",
+        100 => "CountryCode",
+        101 => "Country",
+        103 => "OriginalTransmissionReference",
+        105 => "Headline",
+        110 => "Credit",
+        115 => "Source",
+        116 => "CopyrightNotice",
+        118 => "Contact",
+        120 => "Caption-Abstract",
+        122 => "Writer-Editor",
+        _ => return None,
+    })
+}
+
+/// Pixel density from a JFIF APP0 header
+fn jfif_dpi(path: &Path) -> Result<Option<Dpi>, ProcessError> {
+    let dpi = jpeg_app_segments(path)?
+        .into_iter()
+        .find(|(marker, data)| *marker == 0xe0 && data.starts_with(b"JFIF\0") && data.len() >= 12)
+        .and_then(|(_, data)| {
+            let x = f64::from(u16::from_be_bytes([data[8], data[9]]));
+            let y = f64::from(u16::from_be_bytes([data[10], data[11]]));
+            // Units: 0 = aspect ratio only, 1 = per inch, 2 = per centimeter
+            match data[7] {
+                1 => Some(Dpi { x, y }),
+                2 => Some(Dpi {
+                    x: x * 2.54,
+                    y: y * 2.54,
+                }),
+                _ => None,
+            }
+        });
+
+    Ok(dpi)
+}
+
+/// Pixel density from a PNG `pHYs` chunk
+fn png_dpi(path: &Path) -> Result<Option<Dpi>, ProcessError> {
+    let file = File::open(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let mut reader = BufReader::new(file);
+    let read_err = |e: std::io::Error| ProcessError::ReadError(e.to_string());
+
+    reader.seek(SeekFrom::Start(8)).map_err(read_err)?;
+    let mut header = [0u8; 8];
+    while reader.read_exact(&mut header).is_ok() {
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        match &header[4..8] {
+            b"pHYs" if size == 9 => {
+                let mut data = [0u8; 9];
+                reader.read_exact(&mut data).map_err(read_err)?;
+                // Unit 1 = pixels per meter; 0 only gives the aspect ratio
+                if data[8] != 1 {
+                    return Ok(None);
+                }
+                let x = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                let y = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                return Ok(Some(Dpi {
+                    x: f64::from(x) * 0.0254,
+                    y: f64::from(y) * 0.0254,
+                }));
+            }
+            // pHYs must precede the image data
+            b"IDAT" | b"IEND" => break,
+            _ => {
+                reader
+                    .seek(SeekFrom::Current(i64::from(size) + 4))
+                    .map_err(read_err)?;
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_photoshop_iptc() {
+        let mut iim = Vec::new();
+        for (dataset, value) in [(0u8, &b"\0\x04"[..]), (116, b"(c) Jane"), (25, b"cat")] {
+            iim.extend_from_slice(&[0x1c, 2, dataset]);
+            iim.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            iim.extend_from_slice(value);
+        }
+
+        let mut resources = Vec::new();
+        // An unrelated resource with a 3-byte name (already even with its length byte)
+        resources.extend_from_slice(b"8BIM\x03\xed\x03abc\0\0\0\x01X\0");
+        resources.extend_from_slice(b"8BIM\x04\x04\0\0");
+        resources.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+        resources.extend_from_slice(&iim);
+
+        let iptc = photoshop_resource(&resources, IRB_IPTC).unwrap();
+        let entries = parse_iim(&iptc);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "CopyrightNotice");
+        assert_eq!(entries[0].value, "(c) Jane");
+        assert_eq!(entries[1].name, "Keywords");
+    }
+
+    #[test]
+    fn test_icc_description() {
+        let profile = ColorProfile::new_display_p3().encode().unwrap();
+        assert!(icc_description(&profile).is_some());
+        assert_eq!(icc_description(b"not a profile"), None);
+    }
+}
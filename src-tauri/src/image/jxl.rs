@@ -0,0 +1,287 @@
+use image::DynamicImage;
+use std::path::Path;
+
+use super::formats::InputFormat;
+use super::processor::{ProcessError, ProcessingOptions};
+
+/// Check if a file is a JPEG XL image
+pub fn is_jxl(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| InputFormat::from_extension(&ext.to_string_lossy()))
+        == Some(InputFormat::Jxl)
+}
+
+/// Map a 0-100 quality to the Butteraugli distance libjxl is tuned by
+///
+/// Same curve as `cjxl -q`: 100 is mathematically lossless, 90 is visually lossless.
+pub fn distance_from_quality(quality: u8) -> f32 {
+    let quality = f32::from(quality.min(100));
+    if quality >= 100.0 {
+        0.0
+    } else if quality >= 30.0 {
+        0.1 + (100.0 - quality) * 0.09
+    } else {
+        53.0 / 3000.0 * quality * quality - 23.0 / 20.0 * quality + 25.0
+    }
+}
+
+/// Decode a JPEG XL file (the codestream orientation is applied by libjxl)
+#[cfg(feature = "jxl")]
+pub fn decode(path: &Path) -> Result<DynamicImage, ProcessError> {
+    use image::ImageBuffer;
+    use jpegxl_rs::decode::Pixels;
+    use jpegxl_rs::decoder_builder;
+
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let decoder = decoder_builder()
+        .build()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let (info, pixels) = decoder
+        .decode(&data)
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    let (width, height) = (info.width, info.height);
+    let channels = info.num_color_channels + u32::from(info.has_alpha_channel);
+    let buffer_error = || ProcessError::ReadError("Unexpected JPEG XL buffer size".to_string());
+
+    macro_rules! image {
+        ($data:expr, $luma:ident, $luma_alpha:ident, $rgb:ident, $rgba:ident) => {
+            match channels {
+                1 => DynamicImage::$luma(
+                    ImageBuffer::from_raw(width, height, $data).ok_or_else(buffer_error)?,
+                ),
+                2 => DynamicImage::$luma_alpha(
+                    ImageBuffer::from_raw(width, height, $data).ok_or_else(buffer_error)?,
+                ),
+                3 => DynamicImage::$rgb(
+                    ImageBuffer::from_raw(width, height, $data).ok_or_else(buffer_error)?,
+                ),
+                _ => DynamicImage::$rgba(
+                    ImageBuffer::from_raw(width, height, $data).ok_or_else(buffer_error)?,
+                ),
+            }
+        };
+    }
+
+    let img = match pixels {
+        Pixels::Uint8(data) => image!(data, ImageLuma8, ImageLumaA8, ImageRgb8, ImageRgba8),
+        Pixels::Uint16(data) => image!(data, ImageLuma16, ImageLumaA16, ImageRgb16, ImageRgba16),
+        Pixels::Float(data) => float_image(width, height, channels, data)?,
+        Pixels::Float16(data) => {
+            let data = data.iter().map(|value| value.to_f32()).collect();
+            float_image(width, height, channels, data)?
+        }
+    };
+
+    Ok(img)
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn decode(_path: &Path) -> Result<DynamicImage, ProcessError> {
+    Err(unsupported())
+}
+
+/// Read the ICC profile describing the decoded pixels
+///
+/// libjxl stops at the color encoding in the image header; no pixels are decoded.
+#[cfg(feature = "jxl")]
+pub fn icc_profile(path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    use jpegxl_sys::decode::{JxlDecoderCreate, JxlDecoderDestroy};
+
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    // SAFETY: the decoder is created and destroyed here and only reads `data`
+    unsafe {
+        let decoder = JxlDecoderCreate(std::ptr::null());
+        if decoder.is_null() {
+            return Err(ProcessError::ReadError(
+                "Cannot create JPEG XL decoder".to_string(),
+            ));
+        }
+        let profile = read_color_profile(decoder, &data);
+        JxlDecoderDestroy(decoder);
+        profile
+    }
+}
+
+/// Run the decoder up to the color encoding event and copy out the profile
+///
+/// # Safety
+///
+/// `decoder` must be a valid, freshly created libjxl decoder.
+#[cfg(feature = "jxl")]
+unsafe fn read_color_profile(
+    decoder: *mut jpegxl_sys::decode::JxlDecoder,
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, ProcessError> {
+    use jpegxl_sys::decode::{
+        JxlColorProfileTarget, JxlDecoderCloseInput, JxlDecoderGetColorAsICCProfile,
+        JxlDecoderGetICCProfileSize, JxlDecoderProcessInput, JxlDecoderSetInput, JxlDecoderStatus,
+        JxlDecoderSubscribeEvents,
+    };
+
+    let header_error = || ProcessError::ReadError("Invalid JPEG XL header".to_string());
+    let success = |status| matches!(status, JxlDecoderStatus::Success);
+
+    if !success(JxlDecoderSubscribeEvents(
+        decoder,
+        JxlDecoderStatus::ColorEncoding as i32,
+    )) || !success(JxlDecoderSetInput(decoder, data.as_ptr(), data.len()))
+    {
+        return Err(header_error());
+    }
+    JxlDecoderCloseInput(decoder);
+    if !matches!(
+        JxlDecoderProcessInput(decoder),
+        JxlDecoderStatus::ColorEncoding
+    ) {
+        return Err(header_error());
+    }
+
+    let mut size = 0;
+    if !success(JxlDecoderGetICCProfileSize(
+        decoder,
+        JxlColorProfileTarget::Data,
+        &mut size,
+    )) || size == 0
+    {
+        return Ok(None);
+    }
+    let mut profile = vec![0; size];
+    if !success(JxlDecoderGetColorAsICCProfile(
+        decoder,
+        JxlColorProfileTarget::Data,
+        profile.as_mut_ptr(),
+        size,
+    )) {
+        return Ok(None);
+    }
+    Ok(Some(profile))
+}
+
+/// Without libjxl the file is treated as having no profile, so metadata can still be read
+#[cfg(not(feature = "jxl"))]
+pub fn icc_profile(_path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    Ok(None)
+}
+
+/// Encode pixels as a bare JPEG XL codestream
+///
+/// 16-bit and float images are written with 16 bits per sample.
+#[cfg(feature = "jxl")]
+pub fn encode(img: &DynamicImage, options: &ProcessingOptions) -> Result<Vec<u8>, ProcessError> {
+    use super::processor::CompressionType;
+    use jpegxl_rs::encode::EncoderResult;
+    use jpegxl_rs::encoder_builder;
+
+    let lossless = options.compression == CompressionType::Lossless;
+    let has_alpha = img.color().has_alpha();
+    let mut encoder = encoder_builder()
+        .has_alpha(has_alpha)
+        .lossless(lossless)
+        // Lossless requires keeping the original color space instead of XYB
+        .uses_original_profile(lossless)
+        .quality(distance_from_quality(options.quality))
+        .build()
+        .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+    let (width, height) = (img.width(), img.height());
+    let high_bit_depth = super::dither::is_high_bit_depth(img);
+    let result: Result<Vec<u8>, _> = match (high_bit_depth, has_alpha) {
+        (false, false) => encoder
+            .encode::<u8, u8>(img.to_rgb8().as_raw(), width, height)
+            .map(|r: EncoderResult<u8>| r.data),
+        (false, true) => encoder
+            .encode::<u8, u8>(img.to_rgba8().as_raw(), width, height)
+            .map(|r: EncoderResult<u8>| r.data),
+        (true, false) => encoder
+            .encode::<u16, u16>(img.to_rgb16().as_raw(), width, height)
+            .map(|r: EncoderResult<u16>| r.data),
+        (true, true) => encoder
+            .encode::<u16, u16>(img.to_rgba16().as_raw(), width, height)
+            .map(|r: EncoderResult<u16>| r.data),
+    };
+
+    result.map_err(|e| ProcessError::WriteError(e.to_string()))
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn encode(_img: &DynamicImage, _options: &ProcessingOptions) -> Result<Vec<u8>, ProcessError> {
+    Err(unsupported())
+}
+
+/// Losslessly recompress a JPEG file into JPEG XL
+///
+/// The DCT coefficients are carried over unchanged, so the pixels are identical
+/// to the source JPEG. No container is written, so source metadata is not kept.
+#[cfg(feature = "jxl")]
+pub fn recompress_jpeg(jpeg: &[u8]) -> Result<Vec<u8>, ProcessError> {
+    use jpegxl_rs::encoder_builder;
+
+    let mut encoder = encoder_builder()
+        .use_container(false)
+        .build()
+        .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+    encoder
+        .encode_jpeg(jpeg)
+        .map(|result| result.data)
+        .map_err(|e| ProcessError::WriteError(e.to_string()))
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn recompress_jpeg(_jpeg: &[u8]) -> Result<Vec<u8>, ProcessError> {
+    Err(unsupported())
+}
+
+/// Build an RGB(A) float image; image has no grayscale float buffers
+#[cfg(feature = "jxl")]
+fn float_image(
+    width: u32,
+    height: u32,
+    channels: u32,
+    data: Vec<f32>,
+) -> Result<DynamicImage, ProcessError> {
+    use image::ImageBuffer;
+
+    let data = match channels {
+        1 => data.iter().flat_map(|&l| [l, l, l]).collect(),
+        2 => data
+            .chunks_exact(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        _ => data,
+    };
+    let buffer_error = || ProcessError::ReadError("Unexpected JPEG XL buffer size".to_string());
+
+    Ok(if channels % 2 == 0 {
+        DynamicImage::ImageRgba32F(
+            ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+        )
+    } else {
+        DynamicImage::ImageRgb32F(
+            ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+        )
+    })
+}
+
+#[cfg(not(feature = "jxl"))]
+fn unsupported() -> ProcessError {
+    ProcessError::UnsupportedFormat(
+        "JPEG XL is not included in this build (enable the `jxl` feature)".to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_from_quality() {
+        assert_eq!(distance_from_quality(100), 0.0);
+        assert!((distance_from_quality(90) - 1.0).abs() < 1e-6);
+        assert_eq!(distance_from_quality(0), 25.0);
+        // Lower quality always means a larger distance
+        assert!((0..100).all(|q| distance_from_quality(q) > distance_from_quality(q + 1)));
+    }
+}
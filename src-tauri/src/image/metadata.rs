@@ -0,0 +1,738 @@
+use chrono::{Datelike, FixedOffset, Local, NaiveDateTime, TimeZone};
+use exif::experimental::Writer;
+use exif::{Context, Field, In, Reader, Tag, Value};
+use image::{ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::heif;
+use super::inspect;
+use super::jxl;
+use super::processor::ProcessError;
+use super::svg;
+use super::xmp;
+
+/// TIFF tags that describe the pixel layout of a container rather than the photo.
+/// They are rewritten by the encoder and must not be copied between files.
+const STRUCTURAL_TAGS: &[u16] = &[
+    254, // NewSubfileType
+    255, // SubfileType
+    256, // ImageWidth
+    257, // ImageLength
+    258, // BitsPerSample
+    259, // Compression
+    262, // PhotometricInterpretation
+    273, // StripOffsets
+    277, // SamplesPerPixel
+    278, // RowsPerStrip
+    279, // StripByteCounts
+    284, // PlanarConfiguration
+    317, // Predictor
+    322, // TileWidth
+    323, // TileLength
+    324, // TileOffsets
+    325, // TileByteCounts
+    330, // SubIFDs
+    338, // ExtraSamples
+    339, // SampleFormat
+    347, // JPEGTables
+    513, // JPEGInterchangeFormat
+    514, // JPEGInterchangeFormatLength
+    529, // YCbCrCoefficients
+    530, // YCbCrSubSampling
+    531, // YCbCrPositioning
+];
+
+/// Windows XPAuthor tag (UTF-16LE bytes), written next to Artist for non-ASCII names
+const XP_AUTHOR: Tag = Tag(Context::Tiff, 0x9c9d);
+
+/// TIFF tags holding blobs that are read on their own (XMP, IPTC, Photoshop resources,
+/// ICC profile), so the metadata policy and color conversion decide what is written.
+const BLOB_TAGS: &[u16] = &[700, 33723, 34377, 34675];
+
+/// Which metadata to keep in outputs
+///
+/// The ICC color profile is not affected: it describes how to interpret the
+/// pixels, so it is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataPolicy {
+    /// Remove all metadata except the color profile
+    StripAll,
+    /// Keep all metadata
+    KeepAll,
+    /// Keep only copyright and artist information
+    KeepCopyright,
+    /// Keep everything except GPS location and device serial numbers
+    StripPrivate,
+}
+
+impl MetadataPolicy {
+    /// Check if a category survives this policy
+    pub fn keeps(&self, category: MetadataCategory) -> bool {
+        if category == MetadataCategory::IccProfile {
+            return true;
+        }
+
+        match self {
+            Self::StripAll => false,
+            Self::KeepAll => true,
+            Self::KeepCopyright => category == MetadataCategory::Copyright,
+            // XMP can repeat the GPS and serial number fields
+            Self::StripPrivate => !matches!(
+                category,
+                MetadataCategory::Gps | MetadataCategory::DeviceSerial | MetadataCategory::Xmp
+            ),
+        }
+    }
+}
+
+/// Category of a metadata field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataCategory {
+    /// Artist and Copyright
+    Copyright,
+    /// GPS location
+    Gps,
+    /// Body/lens serial numbers and the vendor MakerNote, which commonly embeds them
+    DeviceSerial,
+    /// Any other EXIF field (camera settings, timestamps, software, ...)
+    Exif,
+    /// XMP packet of the source
+    Xmp,
+    /// IPTC-IIM block of the source
+    Iptc,
+    /// ICC color profile
+    IccProfile,
+}
+
+impl MetadataCategory {
+    /// Classify an EXIF field
+    pub fn of(field: &Field) -> Self {
+        match field.tag {
+            Tag::Artist | Tag::Copyright | XP_AUTHOR => Self::Copyright,
+            Tag::BodySerialNumber | Tag::LensSerialNumber | Tag::MakerNote => Self::DeviceSerial,
+            Tag(Context::Gps, _) => Self::Gps,
+            _ => Self::Exif,
+        }
+    }
+}
+
+/// Fields written into every output that can carry metadata
+///
+/// Values may contain `{year}` (current year) and `{filename}` (source file name
+/// without extension), expanded per file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataFields {
+    /// EXIF Artist (also XMP dc:creator and XPAuthor when not plain ASCII)
+    pub artist: Option<String>,
+    /// EXIF Copyright (also XMP dc:rights when not plain ASCII)
+    pub copyright: Option<String>,
+    /// EXIF ImageDescription (also XMP dc:description when not plain ASCII)
+    pub image_description: Option<String>,
+    /// XMP dc:rights
+    pub rights: Option<String>,
+}
+
+impl MetadataFields {
+    /// Expand placeholders for one source file, dropping blank values
+    pub fn expand(&self, source: &Path) -> Self {
+        let year = chrono::Local::now().year().to_string();
+        let filename = source
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+
+        let expand = |value: &Option<String>| {
+            value
+                .as_deref()
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| v.replace("{year}", &year).replace("{filename}", &filename))
+        };
+
+        Self {
+            artist: expand(&self.artist),
+            copyright: expand(&self.copyright),
+            image_description: expand(&self.image_description),
+            rights: expand(&self.rights),
+        }
+    }
+}
+
+/// Metadata read from a source image
+#[derive(Debug, Clone, Default)]
+pub struct ImageMetadata {
+    /// EXIF fields of the primary image (TIFF, Exif, GPS and Interop IFDs)
+    pub exif_fields: Vec<Field>,
+    /// Byte order of the source EXIF block
+    pub little_endian: bool,
+    /// Embedded ICC color profile
+    pub icc_profile: Option<Vec<u8>>,
+    /// XMP packet to write (only produced by [`ImageMetadata::write_fields`])
+    pub xmp: Option<Vec<u8>>,
+    /// XMP packet of the source, written unless [`ImageMetadata::xmp`] replaces it
+    pub source_xmp: Option<Vec<u8>>,
+    /// Whether the source has IPTC-IIM data, which no output carries over
+    pub has_iptc: bool,
+}
+
+impl ImageMetadata {
+    /// Read metadata from an image file
+    ///
+    /// A file without an EXIF block or color profile yields empty metadata
+    /// rather than an error.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ProcessError> {
+        let path = path.as_ref();
+        let (exif_fields, little_endian) = read_exif(path)?;
+        // Containers image cannot open still have EXIF and a profile worth keeping
+        let (source_xmp, iptc) = inspect::read_xmp_iptc(path).unwrap_or_default();
+
+        Ok(Self {
+            exif_fields,
+            little_endian,
+            icc_profile: read_icc_profile(path)?,
+            xmp: None,
+            source_xmp,
+            has_iptc: iptc.is_some(),
+        })
+    }
+
+    /// Whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.exif_fields.is_empty()
+            && self.icc_profile.is_none()
+            && self.xmp_packet().is_none()
+            && !self.has_iptc
+    }
+
+    /// The XMP packet to write: the one built from the metadata fields, or the source's
+    pub fn xmp_packet(&self) -> Option<&[u8]> {
+        self.xmp.as_deref().or(self.source_xmp.as_deref())
+    }
+
+    /// Whether any EXIF fields are present
+    pub fn has_exif(&self) -> bool {
+        !self.exif_fields.is_empty()
+    }
+
+    /// Categories present in this metadata, in a stable order
+    pub fn categories(&self) -> Vec<MetadataCategory> {
+        let mut categories: Vec<_> = self.exif_fields.iter().map(MetadataCategory::of).collect();
+        if self.icc_profile.is_some() {
+            categories.push(MetadataCategory::IccProfile);
+        }
+        if self.xmp.is_some() {
+            categories.push(MetadataCategory::Copyright);
+        }
+        if self.source_xmp.is_some() {
+            categories.push(MetadataCategory::Xmp);
+        }
+        if self.has_iptc {
+            categories.push(MetadataCategory::Iptc);
+        }
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+
+    /// Drop every field the policy does not keep, returning the removed categories
+    ///
+    /// IPTC data is always reported as removed, as no output writes it.
+    pub fn apply_policy(&mut self, policy: MetadataPolicy) -> Vec<MetadataCategory> {
+        let removed: Vec<_> = self
+            .categories()
+            .into_iter()
+            .filter(|&category| !policy.keeps(category) || category == MetadataCategory::Iptc)
+            .collect();
+
+        self.exif_fields
+            .retain(|field| policy.keeps(MetadataCategory::of(field)));
+        if !policy.keeps(MetadataCategory::Xmp) {
+            self.source_xmp = None;
+        }
+        self.has_iptc = false;
+
+        removed
+    }
+
+    /// Drop everything, including the color profile, returning the removed categories
+    pub fn clear(&mut self) -> Vec<MetadataCategory> {
+        let removed = self.categories();
+        *self = Self::default();
+        removed
+    }
+
+    /// Set the given fields, replacing any copied from the source
+    ///
+    /// Expects placeholders to be expanded already (see [`MetadataFields::expand`]).
+    /// EXIF ASCII fields get `?` for characters outside ASCII; the full text goes
+    /// into an XMP packet (and XPAuthor for the artist).
+    /// Returns the source categories that were replaced as a whole: a new XMP
+    /// packet takes the place of the source's.
+    pub fn write_fields(&mut self, fields: &MetadataFields) -> Vec<MetadataCategory> {
+        let texts = [
+            (Tag::Artist, &fields.artist),
+            (Tag::Copyright, &fields.copyright),
+            (Tag::ImageDescription, &fields.image_description),
+        ];
+        for (tag, value) in texts {
+            if let Some(value) = value {
+                self.exif_fields
+                    .retain(|f| f.tag != tag && !(tag == Tag::Artist && f.tag == XP_AUTHOR));
+                let ascii = value
+                    .chars()
+                    .map(|c| if c.is_ascii() { c } else { '?' })
+                    .collect::<String>();
+                self.exif_fields.push(Field {
+                    tag,
+                    ifd_num: In::PRIMARY,
+                    value: Value::Ascii(vec![ascii.into_bytes()]),
+                });
+            }
+        }
+        if let Some(artist) = fields.artist.as_deref().filter(|a| !a.is_ascii()) {
+            let utf16 = artist.encode_utf16().chain(std::iter::once(0));
+            self.exif_fields.push(Field {
+                tag: XP_AUTHOR,
+                ifd_num: In::PRIMARY,
+                value: Value::Byte(utf16.flat_map(u16::to_le_bytes).collect()),
+            });
+        }
+
+        let mut replaced = Vec::new();
+        let non_ascii = texts
+            .iter()
+            .any(|(_, value)| value.as_deref().is_some_and(|v| !v.is_ascii()));
+        if fields.rights.is_some() || non_ascii {
+            self.xmp = Some(xmp::dc_packet(
+                fields.artist.as_deref(),
+                fields.rights.as_deref().or(fields.copyright.as_deref()),
+                fields.image_description.as_deref(),
+            ));
+            if self.source_xmp.take().is_some() {
+                replaced.push(MetadataCategory::Xmp);
+            }
+        }
+        replaced
+    }
+
+    /// EXIF Orientation value (1-8), if present
+    pub fn orientation(&self) -> Option<u8> {
+        self.exif_fields
+            .iter()
+            .find(|f| f.tag == Tag::Orientation)
+            .and_then(|f| f.value.get_uint(0))
+            .and_then(|o| u8::try_from(o).ok())
+    }
+
+    /// When the photo was taken (EXIF DateTimeOriginal), if recorded
+    ///
+    /// Uses OffsetTimeOriginal when present and the local time zone otherwise.
+    pub fn date_taken(&self) -> Option<SystemTime> {
+        let ascii = |tag: Tag| {
+            self.exif_fields
+                .iter()
+                .find(|f| f.tag == tag)
+                .and_then(|f| match &f.value {
+                    Value::Ascii(v) => v.first(),
+                    _ => None,
+                })
+                .map(|s| {
+                    String::from_utf8_lossy(s)
+                        .trim_end_matches('\0')
+                        .trim()
+                        .to_string()
+                })
+        };
+
+        let taken =
+            NaiveDateTime::parse_from_str(&ascii(Tag::DateTimeOriginal)?, "%Y:%m:%d %H:%M:%S")
+                .ok()?;
+
+        let offset = ascii(Tag::OffsetTimeOriginal).and_then(|o| o.parse::<FixedOffset>().ok());
+
+        match offset {
+            Some(offset) => offset
+                .from_local_datetime(&taken)
+                .single()
+                .map(SystemTime::from),
+            None => Local
+                .from_local_datetime(&taken)
+                .earliest()
+                .map(SystemTime::from),
+        }
+    }
+
+    /// Mark the image as upright by setting Orientation to 1
+    ///
+    /// Used after the pixels have been rotated so viewers do not rotate them again.
+    pub fn reset_orientation(&mut self) {
+        for field in &mut self.exif_fields {
+            if field.tag == Tag::Orientation {
+                field.value = Value::Short(vec![1]);
+            }
+        }
+    }
+
+    /// Encode the EXIF fields as a TIFF-structured block (without the `Exif\0\0` prefix)
+    pub fn exif_block(&self) -> Result<Option<Vec<u8>>, ProcessError> {
+        if !self.has_exif() {
+            return Ok(None);
+        }
+
+        let mut writer = Writer::new();
+        for field in &self.exif_fields {
+            writer.push_field(field);
+        }
+
+        let mut buf = Cursor::new(Vec::new());
+        writer
+            .write(&mut buf, self.little_endian)
+            .map_err(|e| ProcessError::WriteError(format!("Failed to encode EXIF: {}", e)))?;
+
+        Ok(Some(buf.into_inner()))
+    }
+}
+
+/// Read the primary-image EXIF fields and their byte order
+fn read_exif(path: &Path) -> Result<(Vec<Field>, bool), ProcessError> {
+    let file = File::open(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    let exif = match Reader::new().read_from_container(&mut BufReader::new(file)) {
+        Ok(exif) => exif,
+        Err(exif::Error::NotFound(_)) | Err(exif::Error::InvalidFormat(_)) => {
+            return Ok((Vec::new(), false))
+        }
+        Err(e) => return Err(ProcessError::ReadError(e.to_string())),
+    };
+
+    let fields = exif
+        .fields()
+        .filter(|f| f.ifd_num == In::PRIMARY && is_transferable(f))
+        .cloned()
+        .collect();
+
+    Ok((fields, exif.little_endian()))
+}
+
+/// Read the embedded ICC profile (JPEG APP2, PNG iCCP, WebP ICCP, TIFF tag 34675, HEIF colr, JPEG XL)
+///
+/// Only the container headers are parsed; pixels are not decoded.
+pub(super) fn read_icc_profile(path: &Path) -> Result<Option<Vec<u8>>, ProcessError> {
+    if heif::is_heif(path) {
+        return heif::icc_profile(path);
+    }
+    if jxl::is_jxl(path) {
+        return jxl::icc_profile(path);
+    }
+    if svg::is_svg(path) {
+        return Ok(None);
+    }
+
+    let reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    // image's TIFF decoder looks the profile up under an unnamed tag and never finds it
+    if reader.format() == Some(ImageFormat::Tiff) {
+        return read_tiff_tag(path, tiff::tags::Tag::IccProfile);
+    }
+
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    decoder
+        .icc_profile()
+        .map(|profile| profile.filter(|p| !p.is_empty()))
+        .map_err(|e| ProcessError::ReadError(e.to_string()))
+}
+
+/// Read a byte-blob tag from the first directory of a TIFF file
+///
+/// Some writers store blobs such as IPTC as LONG arrays; those are returned as
+/// their big-endian bytes.
+pub(super) fn read_tiff_tag(
+    path: &Path,
+    tag: tiff::tags::Tag,
+) -> Result<Option<Vec<u8>>, ProcessError> {
+    let file = File::open(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(file))
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    let Some(value) = decoder
+        .find_tag(tag)
+        .map_err(|e| ProcessError::ReadError(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+
+    match value.clone().into_u8_vec() {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(_) => value
+            .into_u32_vec()
+            .map(|longs| Some(longs.iter().flat_map(|l| l.to_be_bytes()).collect()))
+            .map_err(|e| ProcessError::ReadError(e.to_string())),
+    }
+}
+
+/// Check if a field can be carried over to a newly encoded image
+fn is_transferable(field: &Field) -> bool {
+    if matches!(field.value, Value::Unknown(..)) {
+        return false;
+    }
+
+    let number = field.tag.number();
+    !(field.tag.context() == Context::Tiff
+        && (STRUCTURAL_TAGS.contains(&number) || BLOB_TAGS.contains(&number)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_structural_tags_are_not_transferable() {
+        assert!(!is_transferable(&field(
+            Tag::ImageWidth,
+            Value::Long(vec![640])
+        )));
+        assert!(!is_transferable(&field(
+            Tag::StripOffsets,
+            Value::Long(vec![8])
+        )));
+        assert!(is_transferable(&field(
+            Tag::Artist,
+            Value::Ascii(vec![b"Jane".to_vec()])
+        )));
+        assert!(!is_transferable(&field(
+            Tag(Context::Tiff, 700),
+            Value::Byte(b"<x:xmpmeta/>".to_vec())
+        )));
+    }
+
+    #[test]
+    fn test_strip_private_drops_tiff_xmp_tag() {
+        let dir = std::env::temp_dir().join(format!("crunch-xmp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source_path, output_path) = (dir.join("source.tif"), dir.join("output.tif"));
+        let xmp_tag = tiff::tags::Tag::from_u16_exhaustive(700);
+
+        let source = ImageMetadata {
+            exif_fields: vec![field(
+                Tag::GPSLatitudeRef,
+                Value::Ascii(vec![b"N".to_vec()]),
+            )],
+            source_xmp: Some(b"<x:xmpmeta>GPS</x:xmpmeta>".to_vec()),
+            ..Default::default()
+        };
+        let img = image::DynamicImage::new_rgb8(2, 2);
+        super::super::tiff_writer::write_tiff(&img, &source_path, &source).unwrap();
+        assert!(read_tiff_tag(&source_path, xmp_tag).unwrap().is_some());
+
+        let mut metadata = ImageMetadata::read(&source_path).unwrap();
+        assert!(metadata.source_xmp.is_some());
+        metadata.apply_policy(MetadataPolicy::StripPrivate);
+        assert!(metadata
+            .exif_fields
+            .iter()
+            .all(|f| f.tag.context() != Context::Gps));
+        super::super::tiff_writer::write_tiff(&img, &output_path, &metadata).unwrap();
+
+        let output_xmp = read_tiff_tag(&output_path, xmp_tag).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(output_xmp, None);
+    }
+
+    #[test]
+    fn test_exif_block_round_trip() {
+        let metadata = ImageMetadata {
+            exif_fields: vec![
+                field(Tag::Make, Value::Ascii(vec![b"Camera".to_vec()])),
+                field(Tag::ExposureTime, Value::Rational(vec![(1, 250).into()])),
+            ],
+            little_endian: true,
+            icc_profile: None,
+            xmp: None,
+            source_xmp: None,
+            has_iptc: false,
+        };
+
+        let block = metadata.exif_block().unwrap().unwrap();
+        let exif = Reader::new().read_raw(block).unwrap();
+
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+        assert!(exif.get_field(Tag::ExposureTime, In::PRIMARY).is_some());
+        assert!(ImageMetadata::default().exif_block().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_apply_policy() {
+        let source = ImageMetadata {
+            exif_fields: vec![
+                field(Tag::Artist, Value::Ascii(vec![b"Jane".to_vec()])),
+                field(Tag::Make, Value::Ascii(vec![b"Camera".to_vec()])),
+                field(Tag::BodySerialNumber, Value::Ascii(vec![b"123".to_vec()])),
+                field(Tag::GPSAltitude, Value::Rational(vec![(10, 1).into()])),
+            ],
+            little_endian: false,
+            icc_profile: None,
+            xmp: None,
+            source_xmp: None,
+            has_iptc: false,
+        };
+
+        let mut metadata = source.clone();
+        let removed = metadata.apply_policy(MetadataPolicy::StripPrivate);
+        assert_eq!(
+            removed,
+            vec![MetadataCategory::Gps, MetadataCategory::DeviceSerial]
+        );
+        assert_eq!(
+            metadata.categories(),
+            vec![MetadataCategory::Copyright, MetadataCategory::Exif]
+        );
+
+        let mut metadata = source.clone();
+        metadata.apply_policy(MetadataPolicy::KeepCopyright);
+        assert_eq!(metadata.categories(), vec![MetadataCategory::Copyright]);
+
+        let mut metadata = source.clone();
+        assert!(metadata.apply_policy(MetadataPolicy::KeepAll).is_empty());
+        assert_eq!(metadata.exif_fields.len(), 4);
+
+        let mut metadata = source;
+        metadata.icc_profile = Some(vec![0; 128]);
+        assert_eq!(metadata.apply_policy(MetadataPolicy::StripAll).len(), 4);
+        assert!(!metadata.has_exif());
+        assert!(metadata.icc_profile.is_some());
+
+        assert_eq!(metadata.clear(), vec![MetadataCategory::IccProfile]);
+        assert!(metadata.is_empty());
+    }
+
+    #[test]
+    fn test_source_xmp_and_iptc() {
+        let source = ImageMetadata {
+            source_xmp: Some(b"<x:xmpmeta/>".to_vec()),
+            has_iptc: true,
+            ..Default::default()
+        };
+
+        // IPTC is reported even when the policy would keep it
+        let mut metadata = source.clone();
+        assert_eq!(
+            metadata.apply_policy(MetadataPolicy::KeepAll),
+            vec![MetadataCategory::Iptc]
+        );
+        assert_eq!(metadata.xmp_packet(), Some(&b"<x:xmpmeta/>"[..]));
+
+        let mut metadata = source.clone();
+        metadata.apply_policy(MetadataPolicy::StripPrivate);
+        assert!(metadata.is_empty());
+
+        // A rights packet replaces the source packet
+        let mut metadata = source;
+        let fields = MetadataFields {
+            rights: Some("Jane".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(metadata.write_fields(&fields), vec![MetadataCategory::Xmp]);
+        assert_ne!(metadata.xmp_packet(), Some(&b"<x:xmpmeta/>"[..]));
+    }
+
+    #[test]
+    fn test_write_fields() {
+        let mut metadata = ImageMetadata {
+            exif_fields: vec![field(Tag::Artist, Value::Ascii(vec![b"Old".to_vec()]))],
+            ..Default::default()
+        };
+        let fields = MetadataFields {
+            artist: Some("{filename} by Jane".to_string()),
+            copyright: Some("  ".to_string()),
+            image_description: None,
+            rights: Some("All rights reserved {year}".to_string()),
+        }
+        .expand(Path::new("/photos/IMG_0001.jpg"));
+
+        assert_eq!(fields.artist.as_deref(), Some("IMG_0001 by Jane"));
+        assert_eq!(fields.copyright, None);
+        assert!(!fields.rights.as_deref().unwrap().contains("{year}"));
+
+        metadata.write_fields(&fields);
+        assert_eq!(metadata.exif_fields.len(), 1);
+        assert!(matches!(
+            &metadata.exif_fields[0].value,
+            Value::Ascii(v) if v[0] == b"IMG_0001 by Jane"
+        ));
+        assert!(metadata.xmp.is_some());
+        assert_eq!(metadata.categories(), vec![MetadataCategory::Copyright]);
+
+        // Non-ASCII names stay intact in XMP and XPAuthor
+        let mut metadata = ImageMetadata::default();
+        metadata.write_fields(&MetadataFields {
+            artist: Some("田中 Zoë".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            &metadata.exif_fields[0].value,
+            Value::Ascii(v) if v[0] == b"?? Zo?"
+        ));
+        assert!(matches!(
+            &metadata.exif_fields[1].value,
+            Value::Byte(v) if v[..2] == [0x30, 0x75]
+        ));
+        let packet = String::from_utf8(metadata.xmp.unwrap()).unwrap();
+        assert!(packet.contains("<rdf:li>田中 Zoë</rdf:li>"));
+    }
+
+    #[test]
+    fn test_date_taken() {
+        let ascii = |s: &str| Value::Ascii(vec![s.as_bytes().to_vec()]);
+        let mut metadata = ImageMetadata {
+            exif_fields: vec![
+                field(Tag::DateTimeOriginal, ascii("2021:06:15 12:30:00")),
+                field(Tag::OffsetTimeOriginal, ascii("+09:00")),
+            ],
+            ..Default::default()
+        };
+        let expected = FixedOffset::east_opt(9 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2021, 6, 15, 12, 30, 0)
+            .unwrap();
+        assert_eq!(metadata.date_taken(), Some(SystemTime::from(expected)));
+
+        metadata.exif_fields[0].value = ascii("0000:00:00 00:00:00");
+        assert_eq!(metadata.date_taken(), None);
+        assert_eq!(ImageMetadata::default().date_taken(), None);
+    }
+
+    #[test]
+    fn test_reset_orientation() {
+        let mut metadata = ImageMetadata {
+            exif_fields: vec![field(Tag::Orientation, Value::Short(vec![6]))],
+            little_endian: false,
+            icc_profile: None,
+            xmp: None,
+            source_xmp: None,
+            has_iptc: false,
+        };
+        assert_eq!(metadata.orientation(), Some(6));
+
+        metadata.reset_orientation();
+        assert_eq!(metadata.orientation(), Some(1));
+        assert_eq!(ImageMetadata::default().orientation(), None);
+    }
+}
@@ -1,2 +1,21 @@
+pub mod animation;
+pub mod color;
+pub mod dither;
 pub mod formats;
+pub mod heif;
+pub mod icon;
+pub mod inspect;
+pub mod jpeg;
+pub mod jxl;
+pub mod metadata;
+pub mod pages;
+pub mod pdf;
+pub mod png_optimizer;
 pub mod processor;
+pub mod quantize;
+pub mod raw;
+pub mod svg;
+pub mod tiff_writer;
+pub mod tonemap;
+pub mod webp_mux;
+pub mod xmp;
@@ -0,0 +1,147 @@
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::formats::InputFormat;
+use super::processor::ProcessError;
+
+/// Upper bound on pages followed, guarding against offset loops
+const MAX_PAGES: usize = 4096;
+
+/// What to do with the pages of a multi-page TIFF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PagePolicy {
+    /// Convert only the first page
+    FirstPage,
+    /// Write every page to its own file with a page-number suffix
+    SplitPages,
+    /// Write all pages into one multi-page file when the output format allows it
+    Combine,
+}
+
+/// Number of pages in a file; 1 for anything but a multi-page TIFF
+pub fn page_count(path: &Path) -> Result<usize, ProcessError> {
+    if InputFormat::from_path(path) != Some(InputFormat::Tiff) {
+        return Ok(1);
+    }
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    Ok(page_offsets(&data).len().max(1))
+}
+
+/// Decode every page of a multi-page TIFF
+///
+/// Returns `None` for other formats and for files with a single page.
+pub fn decode(path: &Path) -> Result<Option<Vec<DynamicImage>>, ProcessError> {
+    if InputFormat::from_path(path) != Some(InputFormat::Tiff) {
+        return Ok(None);
+    }
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    decode_bytes(&data)
+}
+
+fn decode_bytes(data: &[u8]) -> Result<Option<Vec<DynamicImage>>, ProcessError> {
+    let offsets = page_offsets(data);
+    if offsets.len() < 2 {
+        return Ok(None);
+    }
+
+    // The decoder only reads the first directory, so each page is decoded from a
+    // copy whose header points at that page instead
+    let mut patched = data.to_vec();
+    let little_endian = &data[0..2] == b"II";
+    offsets
+        .into_iter()
+        .map(|offset| {
+            let offset = if little_endian {
+                offset.to_le_bytes()
+            } else {
+                offset.to_be_bytes()
+            };
+            patched[4..8].copy_from_slice(&offset);
+            image::load_from_memory_with_format(&patched, ImageFormat::Tiff)
+                .map_err(|e| ProcessError::ReadError(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// Offsets of the top-level image directories of a classic TIFF, in page order
+///
+/// BigTIFF files are reported as having no pages and are read as a single image.
+fn page_offsets(data: &[u8]) -> Vec<u32> {
+    let little_endian = match data.get(0..4) {
+        Some(b"II\x2a\x00") => true,
+        Some(b"MM\x00\x2a") => false,
+        _ => return Vec::new(),
+    };
+    let read = |pos: usize, len: usize| -> Option<u32> {
+        let bytes = data.get(pos..pos + len)?;
+        Some(bytes.iter().enumerate().fold(0, |value, (i, &byte)| {
+            let shift = if little_endian { i } else { len - 1 - i };
+            value | u32::from(byte) << (8 * shift)
+        }))
+    };
+
+    let mut offsets = Vec::new();
+    let mut next = read(4, 4);
+    while let Some(offset) = next.filter(|&offset| offset != 0) {
+        if offsets.contains(&offset) || offsets.len() >= MAX_PAGES {
+            break;
+        }
+        let Some(count) = read(offset as usize, 2) else {
+            break;
+        };
+        offsets.push(offset);
+        next = read(offset as usize + 2 + count as usize * 12, 4);
+    }
+    offsets
+}
+
+/// Output path of one page: `name_page03.png` for page 3 of up to 99 pages
+pub fn page_path(output_path: &Path, page: usize, page_count: usize) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let digits = page_count.to_string().len();
+    let mut name = format!("{}_page{:0digits$}", stem, page);
+    if let Some(ext) = output_path.extension() {
+        name = format!("{}.{}", name, ext.to_string_lossy());
+    }
+    output_path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    #[test]
+    fn test_decode_every_page() {
+        let mut tiff = Vec::new();
+        {
+            let mut encoder = TiffEncoder::new(std::io::Cursor::new(&mut tiff)).unwrap();
+            for (width, value) in [(4, 10u8), (6, 200)] {
+                let page = RgbImage::from_pixel(width, 3, Rgb([value; 3]));
+                encoder
+                    .write_image::<colortype::RGB8>(width, 3, page.as_raw())
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(page_offsets(&tiff).len(), 2);
+        let pages = decode_bytes(&tiff).unwrap().unwrap();
+        assert_eq!(pages[0].width(), 4);
+        assert_eq!(pages[1].width(), 6);
+        assert_eq!(pages[1].to_rgb8().get_pixel(0, 0).0, [200; 3]);
+    }
+
+    #[test]
+    fn test_page_path() {
+        let path = Path::new("/out/scan.png");
+        assert_eq!(page_path(path, 3, 12), Path::new("/out/scan_page03.png"));
+        assert_eq!(page_path(path, 1, 2), Path::new("/out/scan_page1.png"));
+    }
+}
@@ -0,0 +1,361 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, Rgb, RgbImage};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::processor::{ImageProcessor, ProcessError, ProcessingOptions};
+
+/// PDF points per millimetre
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// Page size of a PDF bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfPageSize {
+    /// Each page takes the size of its image, one pixel per point
+    FitImage,
+    A4,
+    Letter,
+}
+
+impl PdfPageSize {
+    /// Portrait width and height in points
+    fn dimensions(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::FitImage => None,
+            Self::A4 => Some((595.28, 841.89)),
+            Self::Letter => Some((612.0, 792.0)),
+        }
+    }
+}
+
+/// How processed images are bundled into one PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfOptions {
+    /// File name of the PDF, written to the output directory
+    pub file_name: String,
+    pub page_size: PdfPageSize,
+    /// Blank border around each image, in millimetres
+    pub margin_mm: f32,
+    /// JPEG quality (0-100) the images are recompressed with
+    pub jpeg_quality: u8,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            file_name: "images.pdf".to_string(),
+            page_size: PdfPageSize::FitImage,
+            margin_mm: 0.0,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+/// One image of a PDF bundle, already compressed
+///
+/// Pages are made from the transformed pixels rather than from the written
+/// files, so every output format works and lossy outputs are not compressed twice.
+pub struct PdfPage {
+    jpeg: Vec<u8>,
+    width: u32,
+    height: u32,
+    is_gray: bool,
+}
+
+impl PdfPage {
+    /// Compress untagged sRGB pixels for the PDF
+    pub fn new(img: &DynamicImage, options: &PdfOptions) -> Result<Self, ProcessError> {
+        let (jpeg, is_gray) = encode_jpeg(img, options.jpeg_quality)?;
+        Ok(Self {
+            jpeg,
+            width: img.width(),
+            height: img.height(),
+            is_gray,
+        })
+    }
+}
+
+/// Decode and transform an input like its processed output, one PDF page per image
+///
+/// Only needed when processing did not decode the input itself.
+pub fn render_pages(
+    input_path: &Path,
+    processing: &ProcessingOptions,
+    options: &PdfOptions,
+) -> Result<Vec<PdfPage>, ProcessError> {
+    ImageProcessor::render_pages(input_path, processing)?
+        .iter()
+        .map(|img| PdfPage::new(img, options))
+        .collect()
+}
+
+/// Write the pages into one PDF, in the given order
+pub fn write_pdf(
+    pages: &[PdfPage],
+    output_path: &Path,
+    options: &PdfOptions,
+) -> Result<(), ProcessError> {
+    let mut bundle = PdfBundle::new(options);
+    for page in pages {
+        bundle.add_page(page);
+    }
+
+    std::fs::write(output_path, bundle.finish()?)
+        .map_err(|e| ProcessError::WriteError(e.to_string()))
+}
+
+/// Reduce a user-given name to a bare `.pdf` file name
+///
+/// Directories, `..` and characters Windows does not allow are removed, so the
+/// PDF always lands in the output directory.
+pub fn file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let stem = if base.to_ascii_lowercase().ends_with(".pdf") {
+        &base[..base.len() - 4]
+    } else {
+        base
+    };
+    let stem: String = stem
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .chars()
+        .map(|c| {
+            if c.is_control() || "<>:\"|?*".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    if stem.is_empty() {
+        "images.pdf".to_string()
+    } else {
+        format!("{}.pdf", stem)
+    }
+}
+
+/// A PDF being built one image page at a time
+struct PdfBundle<'a> {
+    pdf: Pdf,
+    options: &'a PdfOptions,
+    page_ids: Vec<Ref>,
+    next_id: i32,
+}
+
+impl<'a> PdfBundle<'a> {
+    const CATALOG_ID: Ref = Ref::new(1);
+    const PAGE_TREE_ID: Ref = Ref::new(2);
+
+    fn new(options: &'a PdfOptions) -> Self {
+        Self {
+            pdf: Pdf::new(),
+            options,
+            page_ids: Vec::new(),
+            next_id: 3,
+        }
+    }
+
+    fn alloc(&mut self) -> Ref {
+        let id = Ref::new(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    fn add_page(&mut self, img: &PdfPage) {
+        let layout = layout(img.width, img.height, self.options);
+
+        let (page_id, content_id, image_id) = (self.alloc(), self.alloc(), self.alloc());
+        let image_name = Name(b"Im1");
+
+        let mut image = self.pdf.image_xobject(image_id, &img.jpeg);
+        image.filter(Filter::DctDecode);
+        image.width(img.width as i32);
+        image.height(img.height as i32);
+        image.color_space_name(Name(if img.is_gray {
+            b"DeviceGray"
+        } else {
+            b"DeviceRGB"
+        }));
+        image.bits_per_component(8);
+        image.finish();
+
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([layout.width, 0.0, 0.0, layout.height, layout.x, layout.y]);
+        content.x_object(image_name);
+        content.restore_state();
+        self.pdf.stream(content_id, &content.finish());
+
+        let mut page = self.pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, layout.page_width, layout.page_height));
+        page.parent(Self::PAGE_TREE_ID);
+        page.contents(content_id);
+        page.resources().x_objects().pair(image_name, image_id);
+        page.finish();
+
+        self.page_ids.push(page_id);
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, ProcessError> {
+        if self.page_ids.is_empty() {
+            return Err(ProcessError::ProcessingFailed(
+                "No images to put into the PDF".to_string(),
+            ));
+        }
+        self.pdf.catalog(Self::CATALOG_ID).pages(Self::PAGE_TREE_ID);
+        self.pdf
+            .pages(Self::PAGE_TREE_ID)
+            .count(self.page_ids.len() as i32)
+            .kids(self.page_ids.iter().copied());
+        Ok(self.pdf.finish())
+    }
+}
+
+/// Page size and image placement, in points
+#[derive(Debug, PartialEq)]
+struct PageLayout {
+    page_width: f32,
+    page_height: f32,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Scale the image to fit inside the margins, centered
+///
+/// Fixed page sizes turn to landscape for landscape images.
+fn layout(image_width: u32, image_height: u32, options: &PdfOptions) -> PageLayout {
+    let margin = options.margin_mm.max(0.0) * POINTS_PER_MM;
+    let (image_width, image_height) = (image_width as f32, image_height as f32);
+
+    let Some((short, long)) = options.page_size.dimensions() else {
+        return PageLayout {
+            page_width: image_width + 2.0 * margin,
+            page_height: image_height + 2.0 * margin,
+            x: margin,
+            y: margin,
+            width: image_width,
+            height: image_height,
+        };
+    };
+
+    let (page_width, page_height) = if image_width > image_height {
+        (long, short)
+    } else {
+        (short, long)
+    };
+    let scale = ((page_width - 2.0 * margin) / image_width)
+        .min((page_height - 2.0 * margin) / image_height)
+        .max(0.0);
+    let (width, height) = (image_width * scale, image_height * scale);
+
+    PageLayout {
+        page_width,
+        page_height,
+        x: (page_width - width) / 2.0,
+        y: (page_height - height) / 2.0,
+        width,
+        height,
+    }
+}
+
+/// Encode as baseline JPEG, flattening transparency onto white
+///
+/// Returns whether the JPEG is grayscale.
+fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<(Vec<u8>, bool), ProcessError> {
+    let is_gray = !img.color().has_color();
+
+    let mut encoded = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut encoded, quality.clamp(1, 100));
+    let rgb = flatten_on_white(img);
+    let result = if is_gray {
+        let luma = DynamicImage::ImageRgb8(rgb).to_luma8();
+        encoder.write_image(
+            luma.as_raw(),
+            luma.width(),
+            luma.height(),
+            ExtendedColorType::L8,
+        )
+    } else {
+        encoder.write_image(
+            rgb.as_raw(),
+            rgb.width(),
+            rgb.height(),
+            ExtendedColorType::Rgb8,
+        )
+    };
+    result.map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+    Ok((encoded, is_gray))
+}
+
+fn flatten_on_white(img: &DynamicImage) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend =
+            |c: u8| ((u16::from(c) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma, Rgba, RgbaImage};
+
+    #[test]
+    fn test_layout() {
+        let options = PdfOptions {
+            page_size: PdfPageSize::A4,
+            margin_mm: 10.0,
+            ..PdfOptions::default()
+        };
+        // Landscape images turn the page
+        let page = layout(2000, 1000, &options);
+        assert_eq!((page.page_width, page.page_height), (841.89, 595.28));
+        assert!((page.width - (841.89 - 20.0 * POINTS_PER_MM)).abs() < 0.01);
+        assert!((page.y - (595.28 - page.height) / 2.0).abs() < 0.01);
+
+        let fit = layout(300, 200, &PdfOptions::default());
+        assert_eq!(
+            (fit.page_width, fit.page_height, fit.x),
+            (300.0, 200.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("scans"), "scans.pdf");
+        assert_eq!(file_name("Report.PDF"), "Report.pdf");
+        assert_eq!(file_name("../../etc/passwd"), "passwd.pdf");
+        assert_eq!(file_name("C:\\Users\\a\\out.pdf"), "out.pdf");
+        assert_eq!(file_name("/tmp/"), "images.pdf");
+        assert_eq!(file_name(" .. "), "images.pdf");
+        assert_eq!(file_name("a:b?"), "a_b_.pdf");
+    }
+
+    #[test]
+    fn test_bundle_has_a_page_per_image() {
+        let options = PdfOptions::default();
+        let mut bundle = PdfBundle::new(&options);
+        let transparent =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 0])));
+        let gray = DynamicImage::ImageLuma8(GrayImage::from_pixel(4, 4, Luma([0])));
+        bundle.add_page(&PdfPage::new(&transparent, &options).unwrap());
+        bundle.add_page(&PdfPage::new(&gray, &options).unwrap());
+        let pdf = bundle.finish().unwrap();
+
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/DeviceGray"));
+        assert!(PdfBundle::new(&options).finish().is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use oxipng::{indexset, Options, StripChunks};
+use serde::{Deserialize, Serialize};
+
+use super::processor::ProcessError;
+
+/// How hard to work on shrinking PNG output
+///
+/// Every level is lossless: pixels, color profile and the metadata kept by the
+/// metadata policy are unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngOptimization {
+    /// Write the encoder's output as is
+    Off,
+    /// Two filter strategies and a quick deflate pass
+    Fast,
+    /// Four filter strategies with the strongest deflate level
+    Balanced,
+    /// Every filter strategy, each fully compressed before picking one
+    Max,
+}
+
+impl PngOptimization {
+    /// oxipng preset level, `None` when optimization is off
+    fn preset(self) -> Option<u8> {
+        match self {
+            Self::Off => None,
+            Self::Fast => Some(1),
+            Self::Balanced => Some(2),
+            Self::Max => Some(6),
+        }
+    }
+}
+
+/// Re-filter and re-deflate an encoded PNG, reducing its color type and bit depth
+/// where that loses nothing
+///
+/// Ancillary chunks other than display information (color profile, gamut,
+/// physical size, animation) and metadata (EXIF, XMP, text) are dropped.
+pub fn optimize(png: &[u8], level: PngOptimization) -> Result<Vec<u8>, ProcessError> {
+    let Some(preset) = level.preset() else {
+        return Ok(png.to_vec());
+    };
+
+    let mut options = Options::from_preset(preset);
+    options.strip = StripChunks::Keep(indexset! {
+        *b"cICP", *b"iCCP", *b"sRGB", *b"pHYs", *b"acTL", *b"fcTL", *b"fdAT",
+        *b"eXIf", *b"iTXt", *b"tEXt", *b"zTXt",
+    });
+
+    oxipng::optimize_from_memory(png, &options).map_err(|e| ProcessError::WriteError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::png::PngEncoder;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use crate::image::xmp;
+
+    #[test]
+    fn test_optimize_is_lossless_and_smaller() {
+        // Opaque gray pixels in an RGBA image reduce to 8-bit grayscale
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+            let v = ((x / 8 + y / 8) * 16) as u8;
+            Rgba([v, v, v, 255])
+        }));
+        let mut encoded = Vec::new();
+        img.write_with_encoder(PngEncoder::new(&mut encoded))
+            .unwrap();
+        let encoded = xmp::embed_in_png(&encoded, b"<x:xmpmeta/>").unwrap();
+
+        assert_eq!(optimize(&encoded, PngOptimization::Off).unwrap(), encoded);
+        let optimized = optimize(&encoded, PngOptimization::Balanced).unwrap();
+        assert!(optimized.len() < encoded.len());
+        assert!(optimized.windows(4).any(|w| w == b"iTXt"));
+
+        let decoded = image::load_from_memory(&optimized).unwrap();
+        assert_eq!(decoded.to_rgba8(), img.to_rgba8());
+    }
+}
@@ -1,9 +1,32 @@
-use image::{DynamicImage, ImageFormat, ImageReader};
+use image::codecs::avif::{self, AvifEncoder};
+use image::codecs::png::PngEncoder;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageEncoder, ImageFormat, ImageReader};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::fs::{File, FileTimes};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use thiserror::Error;
 
-use super::formats::OutputFormat;
+use super::animation::{self, Animation, AnimationFrame};
+use super::color::{self, ColorManagement};
+use super::dither;
+use super::formats::{InputFormat, OutputFormat};
+use super::heif;
+use super::icon;
+use super::jpeg::{self, ChromaSubsampling, JpegBackend};
+use super::jxl;
+use super::metadata::{ImageMetadata, MetadataCategory, MetadataFields, MetadataPolicy};
+use super::pages::{self, PagePolicy};
+use super::png_optimizer::{self, PngOptimization};
+use super::quantize::{self, Dithering};
+use super::raw;
+use super::svg;
+use super::tiff_writer;
+use super::tonemap;
+use super::webp_mux::{self, WebPChunks};
+use super::xmp;
 
 /// Image processing errors
 #[derive(Error, Debug)]
@@ -19,6 +42,8 @@ pub enum ProcessError {
 }
 
 /// Compression type
+///
+/// Lossy PNG output is reduced to a palette of at most 256 colors.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CompressionType {
@@ -26,6 +51,32 @@ pub enum CompressionType {
     Lossless,
 }
 
+/// AVIF encoder speed (1 = slowest/smallest, 10 = fastest)
+const AVIF_SPEED: u8 = 6;
+
+/// Output bit depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BitDepth {
+    /// Keep 16-bit/float samples when the output format supports them (PNG, TIFF)
+    Preserve,
+    /// Always write 8 bits per channel, dithering high bit depth sources
+    #[serde(rename = "force_8bit")]
+    Force8Bit,
+}
+
+/// Which timestamps the output file gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileTimestamps {
+    /// Leave the time the output was written
+    ProcessingTime,
+    /// Copy modification and access times from the source file
+    CopySource,
+    /// Set the modification time to the EXIF DateTimeOriginal
+    DateTaken,
+}
+
 /// Image processing options
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessingOptions {
@@ -37,10 +88,40 @@ pub struct ProcessingOptions {
     pub width: Option<u32>,
     /// Resize height (None = keep original)
     pub height: Option<u32>,
-    /// Keep metadata (EXIF, etc.)
-    pub keep_metadata: bool,
+    /// Which metadata (EXIF, etc.) to keep
+    pub metadata_policy: MetadataPolicy,
+    /// Artist/copyright fields to write into every output
+    pub metadata_fields: MetadataFields,
+    /// Rotate/flip pixels according to the EXIF Orientation tag
+    pub auto_orient: bool,
+    /// Keep the source color profile or convert pixels to sRGB
+    pub color_management: ColorManagement,
     /// Compression type
     pub compression: CompressionType,
+    /// Encoder used for JPEG output
+    pub jpeg_backend: JpegBackend,
+    /// Chroma subsampling of JPEG and lossy WebP output
+    pub chroma_subsampling: ChromaSubsampling,
+    /// Lossless optimization effort for PNG output
+    pub png_optimization: PngOptimization,
+    /// Dithering of palette output (GIF, lossy PNG)
+    pub dithering: Dithering,
+    /// Bits per channel of the output
+    pub bit_depth: BitDepth,
+    /// Timestamps to set on the output file
+    pub timestamps: FileTimestamps,
+    /// Copy the source file's permission bits to the output
+    pub keep_permissions: bool,
+    /// Square sizes written into ICO/ICNS outputs (empty = default set)
+    pub icon_sizes: Vec<u32>,
+    /// Scale factor for SVG inputs when no width/height is set
+    pub svg_scale: f32,
+    /// Use the camera's embedded JPEG preview of RAW files instead of developing them
+    pub raw_preview: bool,
+    /// Write only the first frame of animated GIF/WebP inputs
+    pub first_frame_only: bool,
+    /// How the pages of multi-page TIFF inputs are written
+    pub page_policy: PagePolicy,
 }
 
 impl Default for ProcessingOptions {
@@ -50,8 +131,23 @@ impl Default for ProcessingOptions {
             quality: 80,
             width: None,
             height: None,
-            keep_metadata: false,
+            metadata_policy: MetadataPolicy::StripAll,
+            metadata_fields: MetadataFields::default(),
+            auto_orient: true,
+            color_management: ColorManagement::Preserve,
             compression: CompressionType::Lossy,
+            jpeg_backend: JpegBackend::Standard,
+            chroma_subsampling: ChromaSubsampling::Auto,
+            png_optimization: PngOptimization::Off,
+            dithering: Dithering::FloydSteinberg,
+            bit_depth: BitDepth::Preserve,
+            timestamps: FileTimestamps::ProcessingTime,
+            keep_permissions: false,
+            icon_sizes: icon::DEFAULT_ICON_SIZES.to_vec(),
+            svg_scale: 1.0,
+            raw_preview: false,
+            first_frame_only: false,
+            page_policy: PagePolicy::FirstPage,
         }
     }
 }
@@ -73,6 +169,12 @@ pub struct ProcessingResult {
     pub success: bool,
     /// Error message if failed
     pub error: Option<String>,
+    /// Metadata categories present in the source but not written to the output
+    pub removed_metadata: Vec<MetadataCategory>,
+    /// Non-fatal issues (e.g. metadata that could not be kept)
+    pub warnings: Vec<String>,
+    /// Files written for each page when a multi-page input was split
+    pub page_outputs: Vec<String>,
 }
 
 /// Image processor
@@ -85,30 +187,168 @@ impl ImageProcessor {
         output_path: Q,
         options: &ProcessingOptions,
     ) -> Result<ProcessingResult, ProcessError> {
-        let input_path = input_path.as_ref();
-        let output_path = output_path.as_ref();
+        Self::process(input_path.as_ref(), output_path.as_ref(), options, false)
+            .map(|(result, _)| result)
+    }
 
+    /// Process a single image, also returning the transformed pages as untagged sRGB
+    ///
+    /// Lets a PDF bundle reuse the decoded pixels. No pages are returned when the
+    /// input was recompressed without decoding (JPEG to lossless JPEG XL).
+    pub fn process_image_with_pages<P: AsRef<Path>, Q: AsRef<Path>>(
+        input_path: P,
+        output_path: Q,
+        options: &ProcessingOptions,
+    ) -> Result<(ProcessingResult, Vec<DynamicImage>), ProcessError> {
+        Self::process(input_path.as_ref(), output_path.as_ref(), options, true)
+    }
+
+    fn process(
+        input_path: &Path,
+        output_path: &Path,
+        options: &ProcessingOptions,
+        keep_pages: bool,
+    ) -> Result<(ProcessingResult, Vec<DynamicImage>), ProcessError> {
         // Get original file size
-        let original_size = std::fs::metadata(input_path)
-            .map_err(|e| ProcessError::ReadError(e.to_string()))?
-            .len();
+        let source_file =
+            std::fs::metadata(input_path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+        let original_size = source_file.len();
 
-        // Load image
-        let img = ImageReader::open(input_path)
-            .map_err(|e| ProcessError::ReadError(e.to_string()))?
-            .decode()
-            .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+        let mut warnings = Vec::new();
+        if options.format == OutputFormat::WebP
+            && options.compression == CompressionType::Lossy
+            && matches!(
+                options.chroma_subsampling,
+                ChromaSubsampling::Chroma444 | ChromaSubsampling::Chroma422
+            )
+        {
+            warnings.push(
+                "Lossy WebP is always 4:2:0; sharper color conversion was used instead".to_string(),
+            );
+        }
+
+        // Read metadata (the color profile is always needed, even with StripAll)
+        let mut metadata = match ImageMetadata::read(input_path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warnings.push(format!("Could not read metadata: {}", e));
+                ImageMetadata::default()
+            }
+        };
+        let orientation = metadata.orientation();
+        // Read before the policy may strip the EXIF date
+        let date_taken = metadata.date_taken();
 
-        // Apply resize if specified
-        let img = Self::apply_resize(img, options);
+        let mut removed_metadata = metadata.apply_policy(options.metadata_policy);
+        removed_metadata.extend(metadata.write_fields(&options.metadata_fields.expand(input_path)));
 
-        // Save with specified format
-        Self::save_image(&img, output_path, options)?;
+        // A JPEG segment holds at most 64 KB; large editor packets are dropped, not fatal
+        if options.format == OutputFormat::Jpeg
+            && metadata.xmp_packet().is_some_and(|p| !xmp::fits_in_jpeg(p))
+        {
+            metadata.xmp = None;
+            metadata.source_xmp = None;
+            removed_metadata.push(MetadataCategory::Xmp);
+            warnings.push("XMP packet is too large for a JPEG segment; it was dropped".to_string());
+        }
+
+        // Animated GIF/WebP/PNG keep every frame when the output can animate too
+        let animation = if options.first_frame_only || !options.format.supports_animation() {
+            None
+        } else {
+            animation::decode(input_path)?
+        };
 
-        // Get output file size
-        let output_size = std::fs::metadata(output_path)
-            .map_err(|e| ProcessError::WriteError(e.to_string()))?
-            .len();
+        // Multi-page TIFFs keep every page unless only the first is wanted
+        let pages = if animation.is_some() || options.page_policy == PagePolicy::FirstPage {
+            None
+        } else {
+            pages::decode(input_path)?
+        };
+
+        let (outputs, rendered) = if let Some(animation) = animation {
+            let animation = Self::save_animation(
+                animation,
+                input_path,
+                output_path,
+                options,
+                &mut metadata,
+                orientation,
+                &mut removed_metadata,
+                &mut warnings,
+            )?;
+            let first_frame = animation.frames.into_iter().next().map(|frame| frame.image);
+            (vec![output_path.to_path_buf()], Vec::from_iter(first_frame))
+        } else if let Some(pages) = pages {
+            let pages = pages
+                .into_iter()
+                .map(|page| {
+                    Self::transform_still(
+                        page,
+                        input_path,
+                        options,
+                        &mut metadata,
+                        orientation,
+                        &mut removed_metadata,
+                        &mut warnings,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let outputs = Self::save_pages(&pages, output_path, options, &metadata, &mut warnings)?;
+            (outputs, pages)
+        } else {
+            let img = Self::process_still(
+                input_path,
+                output_path,
+                options,
+                &mut metadata,
+                orientation,
+                &mut removed_metadata,
+                &mut warnings,
+            )?;
+            (vec![output_path.to_path_buf()], Vec::from_iter(img))
+        };
+
+        // Pixels left in the source profile are converted for device RGB pages;
+        // the output already reported any profile problem
+        let pages = if keep_pages {
+            rendered
+                .into_iter()
+                .map(|page| {
+                    Self::apply_color_management(
+                        page,
+                        ColorManagement::ConvertToSrgbUntagged,
+                        &mut metadata.clone(),
+                        &mut Vec::new(),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            Vec::new()
+        };
+        removed_metadata.sort();
+        removed_metadata.dedup();
+        warnings.dedup();
+
+        let mut output_size = 0;
+        for output in &outputs {
+            Self::apply_file_attributes(&source_file, output, options, date_taken, &mut warnings)?;
+
+            // Get output file size
+            output_size += std::fs::metadata(output)
+                .map_err(|e| ProcessError::WriteError(e.to_string()))?
+                .len();
+        }
+        // Split pages are reported through their own paths
+        let page_outputs = if outputs.len() > 1 {
+            outputs
+                .iter()
+                .map(|output| output.to_string_lossy().to_string())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let output_path = outputs.first().map_or(output_path, PathBuf::as_path);
 
         // Calculate reduction
         let reduction_percent = if original_size > 0 {
@@ -117,7 +357,7 @@ impl ImageProcessor {
             0.0
         };
 
-        Ok(ProcessingResult {
+        let result = ProcessingResult {
             original_path: input_path.to_string_lossy().to_string(),
             output_path: output_path.to_string_lossy().to_string(),
             original_size,
@@ -125,12 +365,429 @@ impl ImageProcessor {
             reduction_percent,
             success: true,
             error: None,
+            removed_metadata,
+            warnings,
+            page_outputs,
+        };
+        Ok((result, pages))
+    }
+
+    /// Decode, transform and save a single-frame image, returning the saved pixels
+    ///
+    /// `orientation` is read before the metadata policy may strip it. Returns
+    /// `None` when the file was recompressed without decoding.
+    fn process_still(
+        input_path: &Path,
+        output_path: &Path,
+        options: &ProcessingOptions,
+        metadata: &mut ImageMetadata,
+        orientation: Option<u8>,
+        removed_metadata: &mut Vec<MetadataCategory>,
+        warnings: &mut Vec<String>,
+    ) -> Result<Option<DynamicImage>, ProcessError> {
+        // A JPEG whose pixels would be left as decoded is recompressed to lossless
+        // JPEG XL without going through pixels at all. Any EXIF orientation goes
+        // through pixels too: libjxl would carry it into the codestream either way.
+        let reoriented = orientation.is_some_and(|o| o > 1);
+        let color_converted =
+            Self::color_management(options).converts() && metadata.icc_profile.is_some();
+        let unchanged =
+            !reoriented && !color_converted && options.width.is_none() && options.height.is_none();
+        if InputFormat::from_path(input_path) == Some(InputFormat::Jpeg)
+            && unchanged
+            && options.format == OutputFormat::Jxl
+            && options.compression == CompressionType::Lossless
+        {
+            Self::drop_metadata(options, metadata, removed_metadata, warnings);
+
+            let jpeg =
+                std::fs::read(input_path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+            std::fs::write(output_path, jxl::recompress_jpeg(&jpeg)?)
+                .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+            return Ok(None);
+        }
+
+        let img = Self::load_still(input_path, options)?;
+        let img = Self::transform_still(
+            img,
+            input_path,
+            options,
+            metadata,
+            orientation,
+            removed_metadata,
+            warnings,
+        )?;
+
+        // Save with specified format
+        Self::save_image(&img, output_path, options, metadata)?;
+        Ok(Some(img))
+    }
+
+    /// Decode and transform an input without saving it, one image per page
+    ///
+    /// Pixels are converted to untagged sRGB, so they can be placed as device RGB.
+    pub fn render_pages(
+        input_path: &Path,
+        options: &ProcessingOptions,
+    ) -> Result<Vec<DynamicImage>, ProcessError> {
+        let options = ProcessingOptions {
+            color_management: ColorManagement::ConvertToSrgbUntagged,
+            ..options.clone()
+        };
+        let mut metadata = ImageMetadata::read(input_path).unwrap_or_default();
+        let orientation = metadata.orientation();
+
+        let pages = if options.page_policy == PagePolicy::FirstPage {
+            None
+        } else {
+            pages::decode(input_path)?
+        };
+        let pages = match pages {
+            Some(pages) => pages,
+            None => vec![Self::load_still(input_path, &options)?],
+        };
+
+        let (mut removed_metadata, mut warnings) = (Vec::new(), Vec::new());
+        pages
+            .into_iter()
+            .map(|page| {
+                Self::transform_still(
+                    page,
+                    input_path,
+                    &options,
+                    &mut metadata,
+                    orientation,
+                    &mut removed_metadata,
+                    &mut warnings,
+                )
+            })
+            .collect()
+    }
+
+    /// Load a single-frame input; vectors are rendered at the output size
+    /// instead of resized later
+    fn load_still(
+        input_path: &Path,
+        options: &ProcessingOptions,
+    ) -> Result<DynamicImage, ProcessError> {
+        match InputFormat::from_path(input_path) {
+            Some(InputFormat::Svg) => {
+                svg::render(input_path, options.width, options.height, options.svg_scale)
+            }
+            Some(InputFormat::Raw) if options.raw_preview => raw::embedded_preview(input_path),
+            _ => Self::load_image(input_path),
+        }
+    }
+
+    /// Tone map, color convert, orient, resize and reduce a decoded image
+    fn transform_still(
+        img: DynamicImage,
+        input_path: &Path,
+        options: &ProcessingOptions,
+        metadata: &mut ImageMetadata,
+        orientation: Option<u8>,
+        removed_metadata: &mut Vec<MetadataCategory>,
+        warnings: &mut Vec<String>,
+    ) -> Result<DynamicImage, ProcessError> {
+        let input_format = InputFormat::from_path(input_path);
+
+        // HDR/EXR hold unbounded linear light; only float TIFF output can keep it
+        let keeps_float =
+            options.format == OutputFormat::Tiff && options.bit_depth == BitDepth::Preserve;
+        let img = if input_format.is_some_and(|f| f.is_scene_linear()) && !keeps_float {
+            tonemap::to_display(&img)
+        } else {
+            img
+        };
+
+        // Convert to sRGB while the source profile is still known
+        let color_management = Self::color_management(options);
+        let had_icc_profile = metadata.icc_profile.is_some();
+        let img = if color_management.converts() {
+            Self::apply_color_management(img, color_management, metadata, warnings)?
+        } else {
+            img
+        };
+        if had_icc_profile && metadata.icc_profile.is_none() {
+            removed_metadata.push(MetadataCategory::IccProfile);
+        }
+
+        if !options.format.supports_metadata() {
+            Self::drop_metadata(options, metadata, removed_metadata, warnings);
+        }
+        // Rotate to the intended orientation before resizing
+        let img = if heif::is_heif(input_path) || jxl::is_jxl(input_path) {
+            // libheif/libjxl already applied the container's rotation; EXIF only mirrors it
+            metadata.reset_orientation();
+            img
+        } else if options.auto_orient {
+            metadata.reset_orientation();
+            Self::apply_orientation(img, orientation)
+        } else {
+            img
+        };
+
+        // Apply resize if specified; vectors were rendered at the output size
+        let img = if input_format == Some(InputFormat::Svg) {
+            img
+        } else {
+            Self::apply_resize(img, options)
+        };
+
+        // Reduce precision last so the steps above work on the full range
+        Ok(match options.bit_depth {
+            BitDepth::Preserve => img,
+            BitDepth::Force8Bit => dither::to_8bit_dithered(img),
         })
     }
 
+    /// Save the pages of a multi-page input combined or as separate files
+    ///
+    /// Returns the files written, in page order.
+    fn save_pages(
+        pages: &[DynamicImage],
+        output_path: &Path,
+        options: &ProcessingOptions,
+        metadata: &ImageMetadata,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<PathBuf>, ProcessError> {
+        if options.page_policy == PagePolicy::Combine {
+            if options.format.supports_pages() {
+                tiff_writer::write_tiff_pages(pages, output_path, metadata)?;
+                return Ok(vec![output_path.to_path_buf()]);
+            }
+            warnings.push(format!(
+                "{} output cannot hold several pages; they were written as separate files",
+                options.format.extension().to_uppercase()
+            ));
+        }
+
+        let mut outputs = Vec::with_capacity(pages.len());
+        for (index, page) in pages.iter().enumerate() {
+            let page_path = pages::page_path(output_path, index + 1, pages.len());
+            Self::save_image(page, &page_path, options, metadata)?;
+            outputs.push(page_path);
+        }
+        Ok(outputs)
+    }
+
+    /// Transform every frame of an animation like a still and save it as animated
+    /// GIF/WebP/PNG, returning the transformed frames
+    #[allow(clippy::too_many_arguments)]
+    fn save_animation(
+        animation: Animation,
+        input_path: &Path,
+        output_path: &Path,
+        options: &ProcessingOptions,
+        metadata: &mut ImageMetadata,
+        orientation: Option<u8>,
+        removed_metadata: &mut Vec<MetadataCategory>,
+        warnings: &mut Vec<String>,
+    ) -> Result<Animation, ProcessError> {
+        // The muxer only handles still WebP, and GIF has no metadata at all, so
+        // their frames are converted to sRGB before the profile is dropped
+        let frame_options = if options.format == OutputFormat::Png {
+            options.clone()
+        } else {
+            ProcessingOptions {
+                color_management: ColorManagement::ConvertToSrgbUntagged,
+                ..options.clone()
+            }
+        };
+
+        // Each frame starts from the source metadata; only the first frame's
+        // changes and reports are kept, as the others repeat them
+        let source_metadata = metadata.clone();
+        let mut first = true;
+        let animation = animation.map_frames(|frame| {
+            let mut frame_metadata = source_metadata.clone();
+            let (mut frame_removed, mut frame_warnings) = (Vec::new(), Vec::new());
+            let frame = Self::transform_still(
+                frame,
+                input_path,
+                &frame_options,
+                &mut frame_metadata,
+                orientation,
+                &mut frame_removed,
+                &mut frame_warnings,
+            )?;
+            if first {
+                *metadata = frame_metadata;
+                removed_metadata.extend(frame_removed);
+                warnings.extend(frame_warnings);
+                first = false;
+            }
+            Ok(frame)
+        })?;
+
+        if options.format != OutputFormat::Png {
+            Self::drop_metadata(options, metadata, removed_metadata, warnings);
+        }
+        let encoded = match options.format {
+            OutputFormat::Gif => animation::encode_gif(&animation, options)?,
+            OutputFormat::Png => animation::encode_png(&animation, metadata)?,
+            _ => animation::encode_webp(&animation, options)?,
+        };
+
+        std::fs::write(output_path, &encoded)
+            .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+        Ok(animation)
+    }
+
+    /// Drop all metadata the output cannot carry, with a warning
+    fn drop_metadata(
+        options: &ProcessingOptions,
+        metadata: &mut ImageMetadata,
+        removed_metadata: &mut Vec<MetadataCategory>,
+        warnings: &mut Vec<String>,
+    ) {
+        if metadata.is_empty() {
+            return;
+        }
+        warnings.push(format!(
+            "{} output cannot carry metadata; it was dropped",
+            options.format.extension().to_uppercase()
+        ));
+        removed_metadata.extend(metadata.clear());
+    }
+
+    /// Color handling for the output format
+    ///
+    /// Formats that cannot embed a profile (AVIF, JPEG XL, GIF, BMP, icons) are
+    /// always converted to sRGB, which is what viewers assume for untagged images;
+    /// otherwise wide-gamut sources would render with the wrong colors.
+    fn color_management(options: &ProcessingOptions) -> ColorManagement {
+        if options.format.supports_metadata() {
+            options.color_management
+        } else {
+            ColorManagement::ConvertToSrgbUntagged
+        }
+    }
+
+    /// Convert pixels from the embedded profile to sRGB and swap the profile to match
+    ///
+    /// Images without a profile are assumed to be sRGB already. When the profile
+    /// cannot be used, the image is left untouched and keeps its original profile.
+    fn apply_color_management(
+        img: DynamicImage,
+        color_management: ColorManagement,
+        metadata: &mut ImageMetadata,
+        warnings: &mut Vec<String>,
+    ) -> Result<DynamicImage, ProcessError> {
+        let Some(icc_profile) = metadata.icc_profile.as_deref() else {
+            return Ok(img);
+        };
+
+        let converted = match color::convert_to_srgb(&img, icc_profile) {
+            Ok(converted) => converted,
+            Err(e) => {
+                warnings.push(format!("{}; the original color profile was kept", e));
+                return Ok(img);
+            }
+        };
+
+        metadata.icc_profile = match color_management {
+            ColorManagement::ConvertToSrgb => Some(color::srgb_profile()?),
+            _ => None,
+        };
+
+        Ok(converted)
+    }
+
+    /// Decode an image file, using libheif for HEIC/AVIF and libjxl for JPEG XL
+    ///
+    /// SVGs are rendered at their own size and camera RAW files are developed.
+    pub fn load_image(path: &Path) -> Result<DynamicImage, ProcessError> {
+        if svg::is_svg(path) {
+            return svg::render(path, None, None, 1.0);
+        }
+        if raw::is_raw(path) {
+            return raw::decode(path);
+        }
+        if heif::is_heif(path) {
+            return heif::decode(path);
+        }
+        if jxl::is_jxl(path) {
+            return jxl::decode(path);
+        }
+
+        // Extensions such as .apng are not known to the decoder, so sniff the content
+        ImageReader::open(path)
+            .map_err(|e| ProcessError::ReadError(e.to_string()))?
+            .with_guessed_format()
+            .map_err(|e| ProcessError::ReadError(e.to_string()))?
+            .decode()
+            .map_err(|e| ProcessError::ReadError(e.to_string()))
+    }
+
+    /// Set timestamps and permission bits of the written file
+    fn apply_file_attributes(
+        source_file: &std::fs::Metadata,
+        output_path: &Path,
+        options: &ProcessingOptions,
+        date_taken: Option<SystemTime>,
+        warnings: &mut Vec<String>,
+    ) -> Result<(), ProcessError> {
+        let times = match options.timestamps {
+            FileTimestamps::ProcessingTime => None,
+            FileTimestamps::CopySource => {
+                let mut times = FileTimes::new();
+                if let Ok(accessed) = source_file.accessed() {
+                    times = times.set_accessed(accessed);
+                }
+                let modified = source_file
+                    .modified()
+                    .map_err(|e| ProcessError::ReadError(e.to_string()))?;
+                Some(times.set_modified(modified))
+            }
+            FileTimestamps::DateTaken => match date_taken {
+                Some(taken) => Some(FileTimes::new().set_modified(taken)),
+                None => {
+                    warnings.push(
+                        "No EXIF DateTimeOriginal; the file keeps its processing time".to_string(),
+                    );
+                    None
+                }
+            },
+        };
+
+        if let Some(times) = times {
+            File::options()
+                .write(true)
+                .open(output_path)
+                .and_then(|file| file.set_times(times))
+                .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+        }
+
+        // Last, as a read-only source would make the output read-only too
+        if options.keep_permissions {
+            std::fs::set_permissions(output_path, source_file.permissions())
+                .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the EXIF Orientation transform so the pixels are stored upright
+    fn apply_orientation(mut img: DynamicImage, orientation: Option<u8>) -> DynamicImage {
+        if let Some(orientation) = orientation.and_then(Orientation::from_exif) {
+            img.apply_orientation(orientation);
+        }
+        img
+    }
+
     /// Apply resize transformation
     fn apply_resize(img: DynamicImage, options: &ProcessingOptions) -> DynamicImage {
-        match (options.width, options.height) {
+        Self::resize(img, options.width, options.height)
+    }
+
+    /// Resize with Lanczos3, keeping the aspect ratio when only one side is given
+    pub(super) fn resize(
+        img: DynamicImage,
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> DynamicImage {
+        match (width, height) {
             (Some(w), Some(h)) => img.resize_exact(w, h, image::imageops::FilterType::Lanczos3),
             (Some(w), None) => {
                 let ratio = w as f64 / img.width() as f64;
@@ -151,25 +808,86 @@ impl ImageProcessor {
         img: &DynamicImage,
         output_path: P,
         options: &ProcessingOptions,
+        metadata: &ImageMetadata,
     ) -> Result<(), ProcessError> {
         let output_path = output_path.as_ref();
+        let exif = metadata.exif_block()?;
 
         match options.format {
             OutputFormat::Jpeg => {
-                let mut file = std::fs::File::create(output_path)
+                let encode = match options.jpeg_backend {
+                    JpegBackend::Standard => jpeg::encode_standard,
+                    JpegBackend::Mozjpeg => jpeg::encode_mozjpeg,
+                };
+                let mut encoded = encode(
+                    &img.to_rgb8(),
+                    options.quality,
+                    options.chroma_subsampling,
+                    metadata.icc_profile.as_deref(),
+                    exif.as_deref(),
+                )?;
+
+                // The encoder has no XMP support, so the segment is spliced in afterwards
+                if let Some(packet) = metadata.xmp_packet() {
+                    encoded = xmp::embed_in_jpeg(&encoded, packet)?;
+                }
+                std::fs::write(output_path, &encoded)
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
-                let encoder =
-                    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, options.quality);
-                img.to_rgb8()
-                    .write_with_encoder(encoder)
+            }
+            OutputFormat::Png if options.compression == CompressionType::Lossy => {
+                let indexed =
+                    quantize::quantize(&img.to_rgba8(), options.quality, options.dithering);
+                let encoded = quantize::encode_png(&indexed, metadata)?;
+                let encoded = png_optimizer::optimize(&encoded, options.png_optimization)?;
+                std::fs::write(output_path, &encoded)
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
             }
             OutputFormat::Png => {
-                img.save_with_format(output_path, ImageFormat::Png)
+                // PNG has no float samples; 16-bit keeps most of the precision
+                let converted;
+                let img = match img {
+                    DynamicImage::ImageRgb32F(_) => {
+                        converted = DynamicImage::ImageRgb16(img.to_rgb16());
+                        &converted
+                    }
+                    DynamicImage::ImageRgba32F(_) => {
+                        converted = DynamicImage::ImageRgba16(img.to_rgba16());
+                        &converted
+                    }
+                    _ => img,
+                };
+
+                let mut encoded = Vec::new();
+                let mut encoder = PngEncoder::new(&mut encoded);
+                if let Some(icc_profile) = &metadata.icc_profile {
+                    encoder
+                        .set_icc_profile(icc_profile.clone())
+                        .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+                }
+                if let Some(exif) = exif {
+                    encoder
+                        .set_exif_metadata(exif)
+                        .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+                }
+                img.write_with_encoder(encoder)
+                    .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+                if let Some(packet) = metadata.xmp_packet() {
+                    encoded = xmp::embed_in_png(&encoded, packet)?;
+                }
+                let encoded = png_optimizer::optimize(&encoded, options.png_optimization)?;
+                std::fs::write(output_path, &encoded)
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
             }
             OutputFormat::Gif => {
-                img.save_with_format(output_path, ImageFormat::Gif)
+                let still = Animation {
+                    frames: vec![AnimationFrame {
+                        image: img.clone(),
+                        delay_ms: 0,
+                    }],
+                    loop_count: 1,
+                };
+                std::fs::write(output_path, animation::encode_gif(&still, options)?)
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
             }
             OutputFormat::Bmp => {
@@ -177,7 +895,46 @@ impl ImageProcessor {
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
             }
             OutputFormat::Tiff => {
-                img.save_with_format(output_path, ImageFormat::Tiff)
+                // Written with the tiff crate directly so metadata can become tags
+                tiff_writer::write_tiff(img, output_path, metadata)?;
+            }
+            OutputFormat::Avif => {
+                let file = File::create(output_path)
+                    .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+                let writer = BufWriter::new(file);
+
+                // ravif has no true lossless mode; the closest is maximum quality
+                // encoded in RGB instead of YCbCr
+                let encoder = if options.compression == CompressionType::Lossless {
+                    AvifEncoder::new_with_speed_quality(writer, AVIF_SPEED, 100)
+                        .with_colorspace(avif::ColorSpace::Srgb)
+                } else {
+                    AvifEncoder::new_with_speed_quality(writer, AVIF_SPEED, options.quality)
+                };
+
+                // Opaque images are encoded without an alpha plane
+                let result = if img.color().has_alpha() {
+                    img.to_rgba8().write_with_encoder(encoder)
+                } else {
+                    img.to_rgb8().write_with_encoder(encoder)
+                };
+                result.map_err(|e| ProcessError::WriteError(e.to_string()))?;
+            }
+            OutputFormat::Jxl => {
+                // Pixels are sRGB by now; other profiles were converted beforehand
+                let encoded = jxl::encode(img, options)?;
+                std::fs::write(output_path, &encoded)
+                    .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+            }
+            OutputFormat::Ico | OutputFormat::Icns => {
+                // Every size is generated from the full image, not from each other
+                let encoded = if options.format == OutputFormat::Ico {
+                    icon::encode_ico(img, &options.icon_sizes)?
+                } else {
+                    icon::encode_icns(img, &options.icon_sizes)?
+                };
+
+                std::fs::write(output_path, &encoded)
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
             }
             OutputFormat::WebP => {
@@ -188,10 +945,30 @@ impl ImageProcessor {
                 let encoded = if options.compression == CompressionType::Lossless {
                     webp::Encoder::from_rgba(&rgba, width, height).encode_lossless()
                 } else {
-                    webp::Encoder::from_rgba(&rgba, width, height).encode(options.quality as f32)
+                    // Lossy WebP is always 4:2:0; sharp RGB to YUV conversion is the
+                    // closest it gets to full color resolution
+                    let mut config = webp::WebPConfig::new().map_err(|_| {
+                        ProcessError::WriteError("Cannot configure WebP encoder".to_string())
+                    })?;
+                    config.quality = f32::from(options.quality);
+                    let subsampling = options.chroma_subsampling.resolve(&img.to_rgb8());
+                    config.use_sharp_yuv = i32::from(subsampling != ChromaSubsampling::Chroma420);
+                    webp::Encoder::from_rgba(&rgba, width, height)
+                        .encode_advanced(&config)
+                        .map_err(|e| ProcessError::WriteError(format!("{:?}", e)))?
                 };
 
-                std::fs::write(output_path, &*encoded)
+                // libwebp writes a bare bitstream; metadata chunks are muxed in afterwards
+                let encoded = webp_mux::mux_webp(
+                    &encoded,
+                    &WebPChunks {
+                        icc_profile: metadata.icc_profile.as_deref(),
+                        exif: exif.as_deref(),
+                        xmp: metadata.xmp_packet(),
+                    },
+                )?;
+
+                std::fs::write(output_path, &encoded)
                     .map_err(|e| ProcessError::WriteError(e.to_string()))?;
             }
         }
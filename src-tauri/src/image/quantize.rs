@@ -0,0 +1,392 @@
+use color_quant::NeuQuant;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::dither;
+use super::metadata::ImageMetadata;
+use super::processor::ProcessError;
+use super::xmp;
+
+/// Smallest palette output is reduced to
+const MIN_COLORS: usize = 8;
+
+/// NeuQuant sampling factor (1 = every pixel, slowest; 30 = fastest)
+const SAMPLE_FACTOR: i32 = 10;
+
+/// How colors between palette entries are approximated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dithering {
+    /// Nearest palette color; flat areas compress best but gradients band
+    None,
+    /// Regular 4x4 pattern; stable between animation frames
+    Ordered,
+    /// Error diffusion; smoothest gradients
+    FloydSteinberg,
+}
+
+/// An image as palette indices
+#[derive(Debug)]
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA entries, at most 256
+    pub palette: Vec<[u8; 4]>,
+    /// Entry used by fully transparent pixels, if any
+    pub transparent: Option<u8>,
+    /// One palette index per pixel, row by row
+    pub indices: Vec<u8>,
+}
+
+/// Palette size for a quality (0-100)
+///
+/// Quality 100 keeps the full 256 colors; lower qualities shrink the palette
+/// quadratically, since the first steps down are barely visible.
+fn palette_size(quality: u8) -> usize {
+    let quality = f64::from(quality.min(100)) / 100.0;
+    ((256.0 * quality * quality).round() as usize).clamp(MIN_COLORS, 256)
+}
+
+/// Dithering strength for a quality (0-100)
+///
+/// Dithering hides banding but its noise costs compression, so lower qualities
+/// dither less.
+fn dither_strength(quality: u8) -> f32 {
+    f32::from(quality.min(100)) / 100.0
+}
+
+/// A palette chosen for the content of one or more images
+///
+/// Fully transparent pixels share one reserved entry, whatever their color.
+pub struct Palette {
+    colors: Vec<[u8; 4]>,
+    transparent: Option<u8>,
+    /// Index of every color when the images use no more colors than the palette holds
+    exact: Option<HashMap<[u8; 4], u8>>,
+    /// Visible entries as (green, index), sorted by green
+    by_green: Vec<(u8, u8)>,
+}
+
+impl Palette {
+    /// Choose a palette for the images, sized by quality (0-100)
+    ///
+    /// Images that already use few enough colors keep them exactly; otherwise
+    /// the palette is trained with NeuQuant.
+    pub fn build(images: &[&RgbaImage], quality: u8) -> Self {
+        let has_transparent = images.iter().any(|img| img.pixels().any(|p| p[3] == 0));
+        let capacity = palette_size(quality) - usize::from(has_transparent);
+        let visible: Vec<[u8; 4]> = images
+            .iter()
+            .flat_map(|img| img.pixels())
+            .filter(|p| p[3] != 0)
+            .map(|p| p.0)
+            .collect();
+
+        let (mut colors, exact) = match exact_colors(&visible, capacity) {
+            Some(colors) => {
+                let lookup = (0..).zip(&colors).map(|(i, c)| (*c, i)).collect();
+                (colors, Some(lookup))
+            }
+            None => {
+                let nq = NeuQuant::new(SAMPLE_FACTOR, capacity, visible.as_flattened());
+                // Entries NeuQuant barely trains keep their translucent start values
+                let opaque = visible.iter().all(|p| p[3] == 255);
+                let colors = nq
+                    .color_map_rgba()
+                    .chunks_exact(4)
+                    .map(|c| [c[0], c[1], c[2], if opaque { 255 } else { c[3] }])
+                    .collect();
+                (colors, None)
+            }
+        };
+        let mut by_green: Vec<(u8, u8)> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c[1], i as u8))
+            .collect();
+        by_green.sort_unstable();
+
+        let transparent = has_transparent.then(|| {
+            colors.push([0; 4]);
+            (colors.len() - 1) as u8
+        });
+
+        Self {
+            colors,
+            transparent,
+            exact,
+            by_green,
+        }
+    }
+
+    /// RGBA entries, the transparent one last
+    pub fn colors(&self) -> &[[u8; 4]] {
+        &self.colors
+    }
+
+    /// Map one of the images the palette was built from to it, dithering with a
+    /// strength set by quality (0-100)
+    ///
+    /// Alpha is matched but not dithered, so transparent and opaque areas stay clean.
+    pub fn index(&self, img: &RgbaImage, quality: u8, dithering: Dithering) -> IndexedImage {
+        let strength = dither_strength(quality);
+        let indices = match (&self.exact, dithering) {
+            // Every color is in the palette, so there is no error to spread
+            (Some(lookup), _) => img
+                .pixels()
+                .map(|p| match (p[3], self.transparent) {
+                    (0, Some(transparent)) => transparent,
+                    _ => lookup.get(&p.0).copied().unwrap_or_default(),
+                })
+                .collect(),
+            (None, Dithering::None) => self.index_ordered(img, 0.0),
+            (None, Dithering::Ordered) => self.index_ordered(img, strength),
+            (None, Dithering::FloydSteinberg) => self.index_diffused(img, strength),
+        };
+
+        IndexedImage {
+            width: img.width(),
+            height: img.height(),
+            palette: self.colors.clone(),
+            transparent: self.transparent,
+            indices,
+        }
+    }
+
+    /// Add a Bayer pattern about as large as the gap between palette colors
+    fn index_ordered(&self, img: &RgbaImage, strength: f32) -> Vec<u8> {
+        let spacing = 256.0 / (self.colors.len() as f32).cbrt();
+        img.enumerate_pixels()
+            .map(|(x, y, p)| {
+                if p[3] == 0 {
+                    return self.transparent.unwrap_or_default();
+                }
+                let offset = dither::bayer_offset(x, y) * spacing * strength;
+                let mut wanted = p.0;
+                for c in wanted.iter_mut().take(3) {
+                    *c = (f32::from(*c) + offset).round().clamp(0.0, 255.0) as u8;
+                }
+                self.nearest(wanted)
+            })
+            .collect()
+    }
+
+    /// Floyd-Steinberg: spread each pixel's color error to its unvisited neighbours
+    fn index_diffused(&self, img: &RgbaImage, strength: f32) -> Vec<u8> {
+        let width = img.width() as usize;
+        // Accumulated error of the current and the next row
+        let mut current = vec![[0f32; 3]; width + 2];
+        let mut next = vec![[0f32; 3]; width + 2];
+        let mut indices = Vec::with_capacity(width * img.height() as usize);
+
+        for row in img.rows() {
+            for (x, p) in row.enumerate() {
+                if p[3] == 0 {
+                    indices.push(self.transparent.unwrap_or_default());
+                    continue;
+                }
+                let mut wanted = p.0;
+                for c in 0..3 {
+                    wanted[c] = (f32::from(p[c]) + current[x + 1][c])
+                        .round()
+                        .clamp(0.0, 255.0) as u8;
+                }
+                let index = self.nearest(wanted);
+                indices.push(index);
+
+                let chosen = self.colors[usize::from(index)];
+                for c in 0..3 {
+                    let error = (f32::from(wanted[c]) - f32::from(chosen[c])) * strength;
+                    current[x + 2][c] += error * 7.0 / 16.0;
+                    next[x][c] += error * 3.0 / 16.0;
+                    next[x + 1][c] += error * 5.0 / 16.0;
+                    next[x + 2][c] += error / 16.0;
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.iter_mut().for_each(|e| *e = [0.0; 3]);
+        }
+
+        indices
+    }
+
+    /// Visible entry closest to a color
+    ///
+    /// Searches outwards from the entries of similar green, stopping in each
+    /// direction once green alone is further off than the best match.
+    fn nearest(&self, color: [u8; 4]) -> u8 {
+        let start = self
+            .by_green
+            .partition_point(|&(green, _)| green < color[1]);
+        let mut best = (u32::MAX, 0);
+        self.scan(self.by_green[start..].iter(), color, &mut best);
+        self.scan(self.by_green[..start].iter().rev(), color, &mut best);
+        best.1
+    }
+
+    /// Update the best (distance, index) from entries ordered by growing green distance
+    fn scan<'a>(
+        &self,
+        entries: impl Iterator<Item = &'a (u8, u8)>,
+        color: [u8; 4],
+        best: &mut (u32, u8),
+    ) {
+        for &(green, index) in entries {
+            if u32::from(green.abs_diff(color[1])).pow(2) >= best.0 {
+                break;
+            }
+            let entry = self.colors[usize::from(index)];
+            let distance = (0..4)
+                .map(|c| u32::from(entry[c].abs_diff(color[c])).pow(2))
+                .sum();
+            if distance < best.0 {
+                *best = (distance, index);
+            }
+        }
+    }
+}
+
+/// The distinct colors, in order of appearance, when there are at most `capacity`
+fn exact_colors(pixels: &[[u8; 4]], capacity: usize) -> Option<Vec<[u8; 4]>> {
+    let mut seen = HashSet::new();
+    let mut colors = Vec::new();
+    for pixel in pixels {
+        if seen.insert(*pixel) {
+            if colors.len() == capacity {
+                return None;
+            }
+            colors.push(*pixel);
+        }
+    }
+    Some(colors)
+}
+
+/// Reduce an image to a palette of its own, sized by quality (0-100)
+pub fn quantize(img: &RgbaImage, quality: u8, dithering: Dithering) -> IndexedImage {
+    Palette::build(&[img], quality).index(img, quality, dithering)
+}
+
+/// Encode an indexed image as an 8-bit palette PNG
+///
+/// Alpha goes into a `tRNS` chunk, trimmed after the last translucent entry.
+pub fn encode_png(
+    indexed: &IndexedImage,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>, ProcessError> {
+    let write_error = |e: png::EncodingError| ProcessError::WriteError(e.to_string());
+
+    let palette: Vec<u8> = indexed
+        .palette
+        .iter()
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+    let mut trns: Vec<u8> = indexed.palette.iter().map(|c| c[3]).collect();
+    while trns.last() == Some(&255) {
+        trns.pop();
+    }
+
+    let mut out = Vec::new();
+    {
+        let mut info = png::Info::with_size(indexed.width, indexed.height);
+        info.color_type = png::ColorType::Indexed;
+        info.bit_depth = png::BitDepth::Eight;
+        info.palette = Some(palette.into());
+        info.trns = (!trns.is_empty()).then(|| trns.into());
+        info.icc_profile = metadata.icc_profile.as_deref().map(Into::into);
+        info.exif_metadata = metadata.exif_block()?.map(Into::into);
+
+        let encoder = png::Encoder::with_info(&mut out, info).map_err(write_error)?;
+        let mut writer = encoder.write_header().map_err(write_error)?;
+        writer
+            .write_image_data(&indexed.indices)
+            .map_err(write_error)?;
+        writer.finish().map_err(write_error)?;
+    }
+
+    match metadata.xmp_packet() {
+        Some(packet) => xmp::embed_in_png(&out, packet),
+        None => Ok(out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |x, y| {
+            Rgba([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8, 255])
+        })
+    }
+
+    #[test]
+    fn test_few_colors_are_kept_exactly() {
+        let img = RgbaImage::from_fn(16, 16, |x, _| match x % 3 {
+            0 => Rgba([255, 0, 0, 255]),
+            1 => Rgba([0, 0, 255, 128]),
+            _ => Rgba([9, 9, 9, 0]),
+        });
+
+        let indexed = quantize(&img, 50, Dithering::FloydSteinberg);
+        assert_eq!(indexed.palette.len(), 3);
+        assert_eq!(indexed.transparent, Some(2));
+        let encoded = encode_png(&indexed, &ImageMetadata::default()).unwrap();
+        let decoded = image::load_from_memory(&encoded).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(decoded.get_pixel(1, 0), &Rgba([0, 0, 255, 128]));
+        assert_eq!(decoded.get_pixel(2, 0)[3], 0);
+    }
+
+    #[test]
+    fn test_quality_chooses_palette_size() {
+        let img = gradient();
+
+        assert_eq!(quantize(&img, 100, Dithering::None).palette.len(), 256);
+        for dithering in [
+            Dithering::None,
+            Dithering::Ordered,
+            Dithering::FloydSteinberg,
+        ] {
+            let small = quantize(&img, 25, dithering);
+            assert_eq!(small.palette.len(), palette_size(25));
+            assert!(small
+                .indices
+                .iter()
+                .all(|&i| usize::from(i) < small.palette.len()));
+        }
+    }
+
+    #[test]
+    fn test_dithering_keeps_average_of_flat_areas() {
+        // A gray ramp trains entries along the gray axis; the top rows are a
+        // flat gray that falls between them
+        let img = RgbaImage::from_fn(64, 64, |x, y| {
+            let v = if y < 8 { 101 } else { (x * 4) as u8 };
+            Rgba([v, v, v, 255])
+        });
+        let palette = Palette::build(&[&img], 10);
+
+        let band = |dithering| {
+            let indexed = palette.index(&img, 100, dithering);
+            let band = &indexed.indices[..64 * 8];
+            let mean = band
+                .iter()
+                .map(|&i| f64::from(indexed.palette[usize::from(i)][0]))
+                .sum::<f64>()
+                / band.len() as f64;
+            (
+                band.iter().collect::<HashSet<_>>().len(),
+                (mean - 101.0).abs(),
+            )
+        };
+        let (plain_colors, plain_error) = band(Dithering::None);
+        assert_eq!(plain_colors, 1);
+        assert!(plain_error > 2.0);
+        assert!(band(Dithering::Ordered).0 > 1);
+        let (diffused_colors, diffused_error) = band(Dithering::FloydSteinberg);
+        assert!(diffused_colors > 1);
+        assert!(diffused_error < 1.0);
+    }
+}
@@ -0,0 +1,468 @@
+use image::{DynamicImage, ImageFormat};
+use std::path::Path;
+
+use super::formats::InputFormat;
+use super::processor::ProcessError;
+
+/// TIFF tags used to locate embedded previews
+const TAG_COMPRESSION: u16 = 259;
+const TAG_STRIP_OFFSETS: u16 = 273;
+const TAG_STRIP_BYTE_COUNTS: u16 = 279;
+const TAG_SUB_IFDS: u16 = 330;
+const TAG_JPEG_OFFSET: u16 = 513;
+const TAG_JPEG_LENGTH: u16 = 514;
+
+/// Upper bound on directories visited, guarding against offset loops
+const MAX_IFDS: usize = 64;
+
+/// Linear sRGB to CIE XYZ (D65)
+#[cfg(any(feature = "raw", test))]
+const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.4124, 0.3576, 0.1805],
+    [0.2126, 0.7152, 0.0722],
+    [0.0193, 0.1192, 0.9505],
+];
+
+/// Check if a file is a camera RAW image
+pub fn is_raw(path: &Path) -> bool {
+    InputFormat::from_path(path) == Some(InputFormat::Raw)
+}
+
+/// Decode the largest JPEG preview the camera embedded in the RAW file
+///
+/// Much faster than developing the sensor data, and already rendered with the
+/// camera's own look. The EXIF orientation still has to be applied.
+pub fn embedded_preview(path: &Path) -> Result<DynamicImage, ProcessError> {
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    // The sensor data itself may be stored as lossless JPEG, which fails to decode
+    // and is skipped in favour of the next candidate
+    let mut previews = find_jpegs(&data);
+    previews.sort_by_key(|jpeg| std::cmp::Reverse(jpeg.len()));
+    previews
+        .into_iter()
+        .find_map(|jpeg| image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).ok())
+        .ok_or_else(|| ProcessError::ReadError("No embedded JPEG preview found".to_string()))
+}
+
+/// Collect JPEG streams referenced from any IFD of a TIFF-based RAW file
+fn find_jpegs(data: &[u8]) -> Vec<&[u8]> {
+    let little_endian = match data.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Vec::new(),
+    };
+    let tiff = TiffReader {
+        data,
+        little_endian,
+    };
+
+    let mut jpegs = Vec::new();
+    let mut pending: Vec<u32> = tiff.u32(4).into_iter().collect();
+    let mut visited = Vec::new();
+
+    while let Some(offset) = pending.pop() {
+        if offset == 0 || visited.contains(&offset) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(offset);
+
+        let offset = offset as usize;
+        let Some(count) = tiff.u16(offset) else {
+            continue;
+        };
+
+        let mut compression = None;
+        let (mut jpeg_offset, mut jpeg_length) = (None, None);
+        let (mut strip_offset, mut strip_length) = (None, None);
+        for i in 0..count as usize {
+            let entry = offset + 2 + i * 12;
+            let (Some(tag), Some(count)) = (tiff.u16(entry), tiff.u32(entry + 4)) else {
+                break;
+            };
+            match tag {
+                TAG_COMPRESSION => compression = tiff.value(entry),
+                TAG_JPEG_OFFSET => jpeg_offset = tiff.value(entry),
+                TAG_JPEG_LENGTH => jpeg_length = tiff.value(entry),
+                // Single-strip images only; previews are never split
+                TAG_STRIP_OFFSETS if count == 1 => strip_offset = tiff.value(entry),
+                TAG_STRIP_BYTE_COUNTS if count == 1 => strip_length = tiff.value(entry),
+                TAG_SUB_IFDS if count == 1 => pending.extend(tiff.value(entry)),
+                TAG_SUB_IFDS => {
+                    if let Some(array) = tiff.u32(entry + 8) {
+                        pending.extend(
+                            (0..count as usize).filter_map(|n| tiff.u32(array as usize + n * 4)),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        pending.extend(tiff.u32(offset + 2 + count as usize * 12));
+
+        // Old-style JPEG (6) and JPEG (7) strips may hold a preview too
+        let strip = match compression {
+            Some(6 | 7) => strip_offset.zip(strip_length),
+            _ => None,
+        };
+        for (start, length) in jpeg_offset.zip(jpeg_length).into_iter().chain(strip) {
+            let (start, end) = (start as usize, start as usize + length as usize);
+            if let Some(jpeg) = data.get(start..end) {
+                if jpeg.starts_with(&[0xff, 0xd8]) {
+                    jpegs.push(jpeg);
+                }
+            }
+        }
+    }
+
+    jpegs
+}
+
+struct TiffReader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn u16(&self, pos: usize) -> Option<u16> {
+        let bytes = self.data.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, pos: usize) -> Option<u32> {
+        let bytes = self.data.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// First value of a SHORT or LONG entry
+    fn value(&self, entry: usize) -> Option<u32> {
+        match self.u16(entry + 2)? {
+            3 => self.u16(entry + 8).map(u32::from),
+            4 | 13 => self.u32(entry + 8),
+            _ => None,
+        }
+    }
+}
+
+/// Develop the sensor data of a RAW file into a 16-bit sRGB image
+#[cfg(feature = "raw")]
+pub fn decode(path: &Path) -> Result<DynamicImage, ProcessError> {
+    use rawloader::RawImageData;
+
+    let raw = rawloader::decode_file(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+
+    let data = match raw.data {
+        RawImageData::Integer(data) => data,
+        RawImageData::Float(_) => {
+            return Err(ProcessError::UnsupportedFormat(
+                "Floating point RAW data is not supported".to_string(),
+            ))
+        }
+    };
+    // rawloader numbers colors R, G, B and a fourth (second green or emerald)
+    let color = |index: usize| if index == 3 { 1 } else { index };
+    let per_color = |values: [f32; 4]| [values[0], values[1], values[2]];
+
+    let sensor = SensorData {
+        width: raw.width,
+        height: raw.height,
+        channels: raw.cpp,
+        data,
+        pattern: (0..raw.cfa.height)
+            .map(|row| {
+                (0..raw.cfa.width)
+                    .map(|col| color(raw.cfa.color_at(row, col)))
+                    .collect()
+            })
+            .collect(),
+        black: per_color(raw.blacklevels.map(f32::from)),
+        white: per_color(raw.whitelevels.map(f32::from)),
+        wb: per_color(raw.wb_coeffs),
+        xyz_to_cam: [raw.xyz_to_cam[0], raw.xyz_to_cam[1], raw.xyz_to_cam[2]],
+    };
+
+    let img = develop(&sensor)?;
+
+    // Masked border pixels are not part of the picture
+    let [top, right, bottom, left] = raw.crops;
+    let width = raw.width.saturating_sub(left + right);
+    let height = raw.height.saturating_sub(top + bottom);
+    if width == 0 || height == 0 {
+        return Ok(img);
+    }
+    Ok(img.crop_imm(left as u32, top as u32, width as u32, height as u32))
+}
+
+/// Without RAW development the camera's embedded preview is used instead
+#[cfg(not(feature = "raw"))]
+pub fn decode(path: &Path) -> Result<DynamicImage, ProcessError> {
+    embedded_preview(path)
+}
+
+/// Undeveloped sensor data
+#[cfg(any(feature = "raw", test))]
+struct SensorData {
+    width: usize,
+    height: usize,
+    /// Samples per pixel: 1 for a color filter array, 3 for linear RGB
+    channels: usize,
+    data: Vec<u16>,
+    /// Repeating filter pattern; 0 = red, 1 = green, 2 = blue
+    pattern: Vec<Vec<usize>>,
+    /// Per-color black and white levels
+    black: [f32; 3],
+    white: [f32; 3],
+    /// As-shot white balance multipliers (NaN when unknown)
+    wb: [f32; 3],
+    /// Camera color matrix (all zero when unknown)
+    xyz_to_cam: [[f32; 3]; 3],
+}
+
+#[cfg(any(feature = "raw", test))]
+impl SensorData {
+    fn color_at(&self, row: usize, col: usize) -> usize {
+        let pattern_row = &self.pattern[row % self.pattern.len()];
+        pattern_row[col % pattern_row.len()]
+    }
+}
+
+/// Basic development: levels, white balance, demosaic, color matrix, tone curve
+#[cfg(any(feature = "raw", test))]
+fn develop(sensor: &SensorData) -> Result<DynamicImage, ProcessError> {
+    let (width, height) = (sensor.width, sensor.height);
+    if sensor.data.len() < width * height * sensor.channels || sensor.channels == 0 {
+        return Err(ProcessError::ReadError(
+            "Truncated RAW sensor data".to_string(),
+        ));
+    }
+
+    // Normalize relative to green so neutral greys end up equal
+    let wb = if sensor.wb.iter().all(|v| v.is_finite() && *v > 0.0) {
+        [
+            sensor.wb[0] / sensor.wb[1],
+            1.0,
+            sensor.wb[2] / sensor.wb[1],
+        ]
+    } else {
+        [1.0; 3]
+    };
+    let level = |value: u16, color: usize| {
+        let range = (sensor.white[color] - sensor.black[color]).max(1.0);
+        // Clip after white balance so blown highlights stay neutral
+        ((f32::from(value) - sensor.black[color]) / range * wb[color]).clamp(0.0, 1.0)
+    };
+
+    let rgb: Vec<[f32; 3]> = if sensor.channels >= 3 {
+        sensor
+            .data
+            .chunks_exact(sensor.channels)
+            .take(width * height)
+            .map(|px| [level(px[0], 0), level(px[1], 1), level(px[2], 2)])
+            .collect()
+    } else {
+        let samples: Vec<f32> = (0..width * height)
+            .map(|i| level(sensor.data[i], sensor.color_at(i / width, i % width)))
+            .collect();
+        demosaic(sensor, &samples)
+    };
+
+    let matrix = camera_to_srgb(&sensor.xyz_to_cam);
+    let data: Vec<u16> = rgb
+        .iter()
+        .flat_map(|px| {
+            matrix.map(|row| {
+                let linear = row[0] * px[0] + row[1] * px[1] + row[2] * px[2];
+                (tone_curve(linear) * 65535.0).round() as u16
+            })
+        })
+        .collect();
+
+    let buf = image::ImageBuffer::from_raw(width as u32, height as u32, data)
+        .ok_or_else(|| ProcessError::ReadError("Unexpected RAW buffer size".to_string()))?;
+    Ok(DynamicImage::ImageRgb16(buf))
+}
+
+/// Bilinear demosaic: missing colors are the mean of neighbouring photosites
+#[cfg(any(feature = "raw", test))]
+fn demosaic(sensor: &SensorData, samples: &[f32]) -> Vec<[f32; 3]> {
+    let (width, height) = (sensor.width, sensor.height);
+    (0..width * height)
+        .map(|i| {
+            let (row, col) = (i / width, i % width);
+            let mut sums = [0.0f32; 3];
+            let mut counts = [0u32; 3];
+            for r in row.saturating_sub(1)..=(row + 1).min(height - 1) {
+                for c in col.saturating_sub(1)..=(col + 1).min(width - 1) {
+                    let color = sensor.color_at(r, c);
+                    sums[color] += samples[r * width + c];
+                    counts[color] += 1;
+                }
+            }
+
+            let own = sensor.color_at(row, col);
+            let mut px = [0.0; 3];
+            for color in 0..3 {
+                px[color] = if color == own {
+                    samples[i]
+                } else if counts[color] > 0 {
+                    sums[color] / counts[color] as f32
+                } else {
+                    0.0
+                };
+            }
+            px
+        })
+        .collect()
+}
+
+/// Matrix from white-balanced camera RGB to linear sRGB (dcraw's method)
+///
+/// Falls back to identity when the camera matrix is unknown.
+#[cfg(any(feature = "raw", test))]
+fn camera_to_srgb(xyz_to_cam: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    let mut cam_to_rgb = [[0.0f32; 3]; 3];
+    for (i, row) in cam_to_rgb.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| xyz_to_cam[i][k] * SRGB_TO_XYZ[k][j]).sum();
+        }
+        // White balance already maps white to (1, 1, 1)
+        let sum: f32 = row.iter().sum();
+        if sum.abs() < f32::EPSILON {
+            return IDENTITY;
+        }
+        row.iter_mut().for_each(|value| *value /= sum);
+    }
+
+    invert(&cam_to_rgb).unwrap_or(IDENTITY)
+}
+
+#[cfg(any(feature = "raw", test))]
+fn invert(m: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if det.abs() < 1e-6 {
+        return None;
+    }
+
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+        (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det
+    };
+    Some([
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ])
+}
+
+/// sRGB transfer followed by a gentle S-curve for contrast
+#[cfg(any(feature = "raw", test))]
+fn tone_curve(linear: f32) -> f32 {
+    let v = if linear.is_nan() {
+        0.0
+    } else {
+        linear.clamp(0.0, 1.0)
+    };
+    let encoded = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    let s_curve = encoded * encoded * (3.0 - 2.0 * encoded);
+    0.75 * encoded + 0.25 * s_curve
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bayer(width: usize, height: usize, values: [u16; 3], wb: [f32; 3]) -> SensorData {
+        let pattern = vec![vec![0, 1], vec![1, 2]];
+        let data = (0..width * height)
+            .map(|i| values[pattern[(i / width) % 2][(i % width) % 2]])
+            .collect();
+        SensorData {
+            width,
+            height,
+            channels: 1,
+            data,
+            pattern,
+            black: [64.0; 3],
+            white: [4095.0; 3],
+            wb,
+            xyz_to_cam: [[0.0; 3]; 3],
+        }
+    }
+
+    #[test]
+    fn test_develop_applies_white_balance() {
+        // Red and blue photosites read half of green under this light
+        let sensor = bayer(6, 6, [1000, 1936, 1000], [2.0, 1.0, 2.0]);
+        let img = develop(&sensor).unwrap();
+        let img = img.as_rgb16().unwrap();
+
+        for px in img.pixels() {
+            assert!(
+                px[0].abs_diff(px[1]) < 200 && px[2].abs_diff(px[1]) < 200,
+                "{:?}",
+                px
+            );
+        }
+        // Neither black nor clipped
+        let grey = img.get_pixel(3, 3)[1];
+        assert!((20000..60000).contains(&grey), "{}", grey);
+    }
+
+    #[test]
+    fn test_camera_to_srgb_round_trips_srgb() {
+        // A camera whose native space is sRGB needs no correction
+        let xyz_to_srgb = invert(&SRGB_TO_XYZ).unwrap();
+        let matrix = camera_to_srgb(&xyz_to_srgb);
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 0.01, "{:?}", matrix);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_jpegs_follows_sub_ifds() {
+        let jpeg = [0xff, 0xd8, 0xff, 0xd9];
+        // IFD0 at 8 points to a SubIFD at 26 holding JPEGInterchangeFormat
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend_from_slice(&[1, 0]);
+        tiff.extend_from_slice(&[0x4a, 0x01, 4, 0, 1, 0, 0, 0, 26, 0, 0, 0]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        tiff.extend_from_slice(&[2, 0]);
+        tiff.extend_from_slice(&[0x01, 0x02, 4, 0, 1, 0, 0, 0, 56, 0, 0, 0]);
+        tiff.extend_from_slice(&[0x02, 0x02, 4, 0, 1, 0, 0, 0, 4, 0, 0, 0]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        tiff.extend_from_slice(&jpeg);
+
+        assert_eq!(find_jpegs(&tiff), vec![&jpeg[..]]);
+        assert!(find_jpegs(b"not a tiff").is_empty());
+    }
+}
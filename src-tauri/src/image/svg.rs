@@ -0,0 +1,148 @@
+use image::{DynamicImage, RgbaImage};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{fontdb, Options, Tree};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use super::formats::InputFormat;
+use super::processor::ProcessError;
+
+/// Check if a file is an SVG drawing
+pub fn is_svg(path: &Path) -> bool {
+    InputFormat::from_path(path) == Some(InputFormat::Svg)
+}
+
+/// System fonts, loaded once for all SVGs with text
+static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+
+/// Render an SVG file directly at the output size
+///
+/// `width`/`height` follow the same rules as resizing raster images: one side
+/// keeps the aspect ratio, both are exact. Without either, the drawing's own
+/// size is multiplied by `scale`.
+pub fn render(
+    path: &Path,
+    width: Option<u32>,
+    height: Option<u32>,
+    scale: f32,
+) -> Result<DynamicImage, ProcessError> {
+    let data = std::fs::read(path).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    // Relative <image> references resolve against the SVG's folder
+    rasterize(&data, path.parent(), width, height, scale)
+}
+
+fn rasterize(
+    data: &[u8],
+    resources_dir: Option<&Path>,
+    width: Option<u32>,
+    height: Option<u32>,
+    scale: f32,
+) -> Result<DynamicImage, ProcessError> {
+    let fontdb = FONTS.get_or_init(|| {
+        let mut fontdb = fontdb::Database::new();
+        fontdb.load_system_fonts();
+        Arc::new(fontdb)
+    });
+    let options = Options {
+        resources_dir: resources_dir.map(Path::to_path_buf),
+        fontdb: fontdb.clone(),
+        ..Options::default()
+    };
+
+    let tree =
+        Tree::from_data(data, &options).map_err(|e| ProcessError::ReadError(e.to_string()))?;
+    let size = tree.size();
+    let (target_width, target_height) =
+        target_size(size.width(), size.height(), width, height, scale)?;
+
+    let mut pixmap = Pixmap::new(target_width, target_height).ok_or_else(|| {
+        ProcessError::ProcessingFailed(format!(
+            "Cannot render SVG at {}x{}",
+            target_width, target_height
+        ))
+    })?;
+    let transform = Transform::from_scale(
+        target_width as f32 / size.width(),
+        target_height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia stores premultiplied alpha
+    let data = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let img = RgbaImage::from_raw(target_width, target_height, data)
+        .ok_or_else(|| ProcessError::ReadError("Unexpected SVG buffer size".to_string()))?;
+
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+fn target_size(
+    svg_width: f32,
+    svg_height: f32,
+    width: Option<u32>,
+    height: Option<u32>,
+    scale: f32,
+) -> Result<(u32, u32), ProcessError> {
+    let (w, h) = match (width, height) {
+        (Some(w), Some(h)) => (w as f32, h as f32),
+        (Some(w), None) => (w as f32, svg_height * w as f32 / svg_width),
+        (None, Some(h)) => (svg_width * h as f32 / svg_height, h as f32),
+        (None, None) => (svg_width * scale, svg_height * scale),
+    };
+
+    let (w, h) = (w.round(), h.round());
+    if !(w >= 1.0 && h >= 1.0) {
+        return Err(ProcessError::ProcessingFailed(format!(
+            "Invalid SVG render size {}x{}",
+            w, h
+        )));
+    }
+    Ok((w as u32, h as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_size() {
+        assert_eq!(
+            target_size(100.0, 50.0, Some(400), None, 1.0).unwrap(),
+            (400, 200)
+        );
+        assert_eq!(
+            target_size(100.0, 50.0, None, Some(25), 1.0).unwrap(),
+            (50, 25)
+        );
+        assert_eq!(
+            target_size(100.0, 50.0, Some(10), Some(10), 1.0).unwrap(),
+            (10, 10)
+        );
+        assert_eq!(
+            target_size(100.0, 50.0, None, None, 2.5).unwrap(),
+            (250, 125)
+        );
+        assert!(target_size(100.0, 50.0, None, None, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_render_is_sharp_at_large_sizes() {
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+            <rect width="5" height="10" fill="#ff0000"/>
+        </svg>"##;
+
+        let img = rasterize(svg, None, Some(1000), None, 1.0)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(img.dimensions(), (1000, 1000));
+        // The edge stays a hard step instead of an upscaled blur
+        assert_eq!(img.get_pixel(498, 500).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(502, 500).0[3], 0);
+    }
+}
@@ -0,0 +1,360 @@
+use exif::{Context, Field, Value};
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+use tiff::encoder::colortype::{
+    ColorType, Gray16, Gray8, RGB32Float, RGBA32Float, RGB16, RGB8, RGBA16, RGBA8,
+};
+use tiff::encoder::{DirectoryEncoder, TiffEncoder, TiffKindStandard, TiffValue};
+use tiff::tags::{Tag, Type};
+use tiff::TiffResult;
+
+use super::metadata::ImageMetadata;
+use super::processor::ProcessError;
+
+const EXIF_IFD_POINTER: u16 = 34665;
+const GPS_IFD_POINTER: u16 = 34853;
+const INTEROP_IFD_POINTER: u16 = 40965;
+const XMP: u16 = 700;
+const ICC_PROFILE: u16 = 34675;
+
+type Directory<'a, W> = DirectoryEncoder<'a, W, TiffKindStandard>;
+
+/// Write an image as TIFF, storing metadata as tags of the image directory
+///
+/// EXIF fields from the Exif, GPS and Interop contexts go into their own sub-IFDs,
+/// linked from the image directory just like in a camera-produced TIFF.
+pub fn write_tiff<P: AsRef<Path>>(
+    img: &DynamicImage,
+    output_path: P,
+    metadata: &ImageMetadata,
+) -> Result<(), ProcessError> {
+    write_tiff_pages(std::slice::from_ref(img), output_path, metadata)
+}
+
+/// Write several images as the pages of one TIFF
+///
+/// Metadata is stored with the first page only.
+pub fn write_tiff_pages<P: AsRef<Path>>(
+    pages: &[DynamicImage],
+    output_path: P,
+    metadata: &ImageMetadata,
+) -> Result<(), ProcessError> {
+    let file = std::fs::File::create(output_path.as_ref())
+        .map_err(|e| ProcessError::WriteError(e.to_string()))?;
+    let mut writer = BufWriter::new(file);
+
+    encode(pages, &mut writer, metadata).map_err(|e| ProcessError::WriteError(e.to_string()))?;
+
+    writer
+        .flush()
+        .map_err(|e| ProcessError::WriteError(e.to_string()))
+}
+
+fn encode<W: Write + Seek>(
+    pages: &[DynamicImage],
+    writer: &mut W,
+    metadata: &ImageMetadata,
+) -> TiffResult<()> {
+    let mut encoder = TiffEncoder::new(writer)?;
+
+    let fields_in = |context: Context| -> Vec<&Field> {
+        metadata
+            .exif_fields
+            .iter()
+            .filter(|f| f.tag.context() == context)
+            .collect()
+    };
+
+    // Sub-IFDs are written first so the image directory can point at them
+    let interop_ifd = write_sub_ifd(&mut encoder, &fields_in(Context::Interop), &[])?;
+    let exif_pointers: Vec<(u16, u32)> = interop_ifd
+        .map(|offset| (INTEROP_IFD_POINTER, offset))
+        .into_iter()
+        .collect();
+    let exif_ifd = write_sub_ifd(&mut encoder, &fields_in(Context::Exif), &exif_pointers)?;
+    let gps_ifd = write_sub_ifd(&mut encoder, &fields_in(Context::Gps), &[])?;
+
+    let mut pointers = Vec::new();
+    if let Some(offset) = exif_ifd {
+        pointers.push((EXIF_IFD_POINTER, offset));
+    }
+    if let Some(offset) = gps_ifd {
+        pointers.push((GPS_IFD_POINTER, offset));
+    }
+
+    // Only the profile and packet decided for this output are written, never stale copies
+    let mut fields = fields_in(Context::Tiff);
+    fields.retain(|f| ![ICC_PROFILE, XMP].contains(&f.tag.number()));
+
+    let tags = ImageTags {
+        fields,
+        pointers,
+        icc_profile: metadata.icc_profile.as_deref(),
+        xmp: metadata.xmp_packet(),
+    };
+    let no_tags = ImageTags {
+        fields: Vec::new(),
+        pointers: Vec::new(),
+        icc_profile: None,
+        xmp: None,
+    };
+
+    for (index, img) in pages.iter().enumerate() {
+        let tags = if index == 0 { &tags } else { &no_tags };
+        write_page(&mut encoder, img, tags)?;
+    }
+    Ok(())
+}
+
+/// Write one image directory in the closest TIFF color type
+fn write_page<W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    img: &DynamicImage,
+    tags: &ImageTags,
+) -> TiffResult<()> {
+    let (width, height) = (img.width(), img.height());
+
+    macro_rules! write_as {
+        ($color:ty, $data:expr) => {
+            write_image::<$color, _>(encoder, width, height, $data, tags)
+        };
+    }
+
+    match img {
+        DynamicImage::ImageLuma8(buf) => write_as!(Gray8, buf),
+        DynamicImage::ImageLuma16(buf) => write_as!(Gray16, buf),
+        DynamicImage::ImageRgb8(buf) => write_as!(RGB8, buf),
+        DynamicImage::ImageRgba8(buf) => write_as!(RGBA8, buf),
+        DynamicImage::ImageRgb16(buf) => write_as!(RGB16, buf),
+        DynamicImage::ImageRgba16(buf) => write_as!(RGBA16, buf),
+        DynamicImage::ImageRgb32F(buf) => write_as!(RGB32Float, buf),
+        DynamicImage::ImageRgba32F(buf) => write_as!(RGBA32Float, buf),
+        DynamicImage::ImageLumaA16(_) => write_as!(RGBA16, &img.to_rgba16()),
+        _ => write_as!(RGBA8, &img.to_rgba8()),
+    }
+}
+
+/// Tags written into the image directory
+struct ImageTags<'a> {
+    /// TIFF-context EXIF fields
+    fields: Vec<&'a Field>,
+    /// Offsets of the sub-IFDs, keyed by pointer tag
+    pointers: Vec<(u16, u32)>,
+    icc_profile: Option<&'a [u8]>,
+    xmp: Option<&'a [u8]>,
+}
+
+/// Write one strip-based image directory
+fn write_image<C: ColorType, W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    width: u32,
+    height: u32,
+    data: &[C::Inner],
+    tags: &ImageTags,
+) -> TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    let mut image = encoder.new_image::<C>(width, height)?;
+    for field in &tags.fields {
+        write_field(image.encoder(), field)?;
+    }
+    for &(tag, offset) in &tags.pointers {
+        image
+            .encoder()
+            .write_tag(Tag::from_u16_exhaustive(tag), offset)?;
+    }
+    if let Some(profile) = tags.icc_profile {
+        image
+            .encoder()
+            .write_tag(Tag::from_u16_exhaustive(ICC_PROFILE), Undefined(profile))?;
+    }
+    if let Some(packet) = tags.xmp {
+        image
+            .encoder()
+            .write_tag(Tag::from_u16_exhaustive(XMP), packet)?;
+    }
+    image.write_data(data)
+}
+
+/// Write a directory that is not part of the image chain, returning its offset
+fn write_sub_ifd<W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    fields: &[&Field],
+    pointers: &[(u16, u32)],
+) -> TiffResult<Option<u32>> {
+    if fields.is_empty() && pointers.is_empty() {
+        return Ok(None);
+    }
+
+    let mut directory = encoder.extra_directory()?;
+    for field in fields {
+        write_field(&mut directory, field)?;
+    }
+    for &(tag, offset) in pointers {
+        directory.write_tag(Tag::from_u16_exhaustive(tag), offset)?;
+    }
+
+    Ok(Some(directory.finish_with_offsets()?.offset))
+}
+
+/// Write a single EXIF field, keeping its original TIFF type
+fn write_field<W: Write + Seek>(directory: &mut Directory<W>, field: &Field) -> TiffResult<()> {
+    let tag = Tag::from_u16_exhaustive(field.tag.number());
+
+    match &field.value {
+        Value::Byte(v) => directory.write_tag(tag, v.as_slice()),
+        Value::Ascii(v) => directory.write_tag(tag, Ascii(v)),
+        Value::Short(v) => directory.write_tag(tag, v.as_slice()),
+        Value::Long(v) => directory.write_tag(tag, v.as_slice()),
+        Value::Rational(v) => directory.write_tag(tag, Rationals(v)),
+        Value::SByte(v) => directory.write_tag(tag, v.as_slice()),
+        Value::Undefined(v, _) => directory.write_tag(tag, Undefined(v)),
+        Value::SShort(v) => directory.write_tag(tag, v.as_slice()),
+        Value::SLong(v) => directory.write_tag(tag, v.as_slice()),
+        Value::SRational(v) => directory.write_tag(tag, SRationals(v)),
+        Value::Float(v) => directory.write_tag(tag, v.as_slice()),
+        Value::Double(v) => directory.write_tag(tag, v.as_slice()),
+        Value::Unknown(..) => Ok(()),
+    }
+}
+
+/// NUL-terminated ASCII strings
+struct Ascii<'a>(&'a [Vec<u8>]);
+
+impl TiffValue for Ascii<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: Type = Type::ASCII;
+
+    fn count(&self) -> usize {
+        self.0.iter().map(|s| s.len() + 1).sum()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(
+            self.0
+                .iter()
+                .flat_map(|s| s.iter().copied().chain(std::iter::once(0)))
+                .collect(),
+        )
+    }
+}
+
+/// Opaque bytes (EXIF `UNDEFINED` type)
+struct Undefined<'a>(&'a [u8]);
+
+impl TiffValue for Undefined<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: Type = Type::UNDEFINED;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0)
+    }
+}
+
+/// Unsigned rationals, any count
+struct Rationals<'a>(&'a [exif::Rational]);
+
+impl TiffValue for Rationals<'_> {
+    const BYTE_LEN: u8 = 8;
+    const FIELD_TYPE: Type = Type::RATIONAL;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(
+            self.0
+                .iter()
+                .flat_map(|r| [r.num.to_ne_bytes(), r.denom.to_ne_bytes()])
+                .flatten()
+                .collect(),
+        )
+    }
+}
+
+/// Signed rationals, any count
+struct SRationals<'a>(&'a [exif::SRational]);
+
+impl TiffValue for SRationals<'_> {
+    const BYTE_LEN: u8 = 8;
+    const FIELD_TYPE: Type = Type::SRATIONAL;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(
+            self.0
+                .iter()
+                .flat_map(|r| [r.num.to_ne_bytes(), r.denom.to_ne_bytes()])
+                .flatten()
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{In, Reader};
+    use std::io::Cursor;
+    use tiff::decoder::Decoder;
+
+    #[test]
+    fn test_round_trip_keeps_icc_xmp_and_exif() {
+        let field = |tag, value| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        };
+        let mut metadata = ImageMetadata {
+            exif_fields: vec![
+                field(exif::Tag::Artist, Value::Ascii(vec![b"Jane".to_vec()])),
+                // A stale profile copied from the source must never be written
+                field(
+                    exif::Tag(Context::Tiff, ICC_PROFILE),
+                    Value::Undefined(vec![1], 0),
+                ),
+            ],
+            icc_profile: Some(b"profile".to_vec()),
+            source_xmp: Some(b"<x:xmpmeta/>".to_vec()),
+            ..Default::default()
+        };
+        let write = |metadata: &ImageMetadata| {
+            let mut buf = Cursor::new(Vec::new());
+            encode(&[DynamicImage::new_rgb8(2, 2)], &mut buf, metadata).unwrap();
+            buf.into_inner()
+        };
+
+        let data = write(&metadata);
+        let mut decoder = Decoder::new(Cursor::new(&data)).unwrap();
+        let mut blob = |tag| {
+            decoder
+                .get_tag_u8_vec(Tag::from_u16_exhaustive(tag))
+                .unwrap()
+        };
+        assert_eq!(blob(ICC_PROFILE), b"profile");
+        assert_eq!(blob(XMP), b"<x:xmpmeta/>");
+
+        let exif = Reader::new().read_raw(data).unwrap();
+        let artist = exif.get_field(exif::Tag::Artist, In::PRIMARY).unwrap();
+        assert!(matches!(&artist.value, Value::Ascii(v) if v[0] == b"Jane"));
+
+        metadata.icc_profile = None;
+        let data = write(&metadata);
+        let mut decoder = Decoder::new(Cursor::new(&data)).unwrap();
+        let icc = decoder
+            .find_tag(Tag::from_u16_exhaustive(ICC_PROFILE))
+            .unwrap();
+        assert!(icc.is_none());
+    }
+}
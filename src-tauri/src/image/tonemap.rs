@@ -0,0 +1,113 @@
+use image::{DynamicImage, ImageBuffer, Rgba};
+
+/// Rec. 709 luminance weights
+const LUMA_WEIGHTS: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Map scene-linear float pixels (Radiance HDR, OpenEXR) to 16-bit sRGB
+///
+/// Uses extended Reinhard on luminance with the brightest pixel as white point,
+/// so highlights roll off instead of clipping and hues are kept. Images that
+/// already fit in 0..1 are only sRGB-encoded. Alpha is clamped, not mapped.
+pub fn to_display(img: &DynamicImage) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let mut buf = img.to_rgba32f();
+
+    let luminance = |p: &Rgba<f32>| -> f32 {
+        let l = LUMA_WEIGHTS[0] * p[0] + LUMA_WEIGHTS[1] * p[1] + LUMA_WEIGHTS[2] * p[2];
+        if l.is_finite() {
+            l.max(0.0)
+        } else {
+            0.0
+        }
+    };
+    let white = buf.pixels().map(luminance).fold(0.0f32, f32::max);
+
+    for pixel in buf.pixels_mut() {
+        let l = luminance(pixel);
+        let scale = if white > 1.0 && l > 0.0 {
+            let mapped = l * (1.0 + l / (white * white)) / (1.0 + l);
+            mapped / l
+        } else {
+            1.0
+        };
+        for c in 0..3 {
+            pixel[c] = encode_srgb(pixel[c] * scale);
+        }
+        pixel[3] = sanitize(pixel[3]);
+    }
+
+    let (width, height) = buf.dimensions();
+    let data: Vec<u16> = buf
+        .into_raw()
+        .into_iter()
+        .map(|v| (v * 65535.0).round() as u16)
+        .collect();
+    let rgba: ImageBuffer<Rgba<u16>, _> =
+        ImageBuffer::from_raw(width, height, data).expect("tone mapped buffer size");
+
+    let rgba = DynamicImage::ImageRgba16(rgba);
+    if has_alpha {
+        rgba
+    } else {
+        DynamicImage::ImageRgb16(rgba.to_rgb16())
+    }
+}
+
+/// Linear light to the sRGB transfer curve, clamped to 0..1
+fn encode_srgb(linear: f32) -> f32 {
+    let v = sanitize(linear);
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn sanitize(value: f32) -> f32 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_to_display_rolls_off_highlights() {
+        let mut img = ImageBuffer::from_pixel(2, 1, Rgb([0.18f32, 0.18, 0.18]));
+        img.put_pixel(1, 0, Rgb([16.0, 8.0, 4.0]));
+
+        let out = to_display(&DynamicImage::ImageRgb32F(img));
+        let out = out.as_rgb16().unwrap();
+
+        // Mid grey stays mid grey, darker than plain sRGB encoding (~0.46)
+        let grey = out.get_pixel(0, 0);
+        assert!(grey[0] == grey[1] && grey[1] == grey[2]);
+        assert!((20000..30000).contains(&grey[0]), "{:?}", grey);
+
+        // The brightest pixel maps to white luminance without losing its hue
+        let bright = out.get_pixel(1, 0);
+        assert!(
+            bright[0] > bright[1] && bright[1] > bright[2],
+            "{:?}",
+            bright
+        );
+    }
+
+    #[test]
+    fn test_to_display_keeps_low_dynamic_range() {
+        let img = ImageBuffer::from_pixel(1, 1, Rgba([0.5f32, 0.0, 1.0, 0.25]));
+        let out = to_display(&DynamicImage::ImageRgba32F(img));
+        let pixel = out.as_rgba16().unwrap().get_pixel(0, 0);
+
+        assert_eq!(pixel[1], 0);
+        assert_eq!(pixel[2], 65535);
+        // sRGB-encoded 0.5 is ~0.735
+        assert!((pixel[0] as i32 - 48190).abs() < 100, "{:?}", pixel);
+        assert_eq!(pixel[3], 16384);
+    }
+}
@@ -0,0 +1,241 @@
+use super::processor::ProcessError;
+
+const VP8X_FLAG_XMP: u8 = 0x04;
+const VP8X_FLAG_EXIF: u8 = 0x08;
+const VP8X_FLAG_ALPHA: u8 = 0x10;
+const VP8X_FLAG_ICC: u8 = 0x20;
+
+/// Metadata chunks to attach to an encoded WebP file
+#[derive(Debug, Default)]
+pub struct WebPChunks<'a> {
+    /// ICC color profile
+    pub icc_profile: Option<&'a [u8]>,
+    /// TIFF-structured EXIF block
+    pub exif: Option<&'a [u8]>,
+    /// XMP packet
+    pub xmp: Option<&'a [u8]>,
+}
+
+impl WebPChunks<'_> {
+    fn is_empty(&self) -> bool {
+        self.icc_profile.is_none() && self.exif.is_none() && self.xmp.is_none()
+    }
+}
+
+/// A RIFF chunk borrowed from an encoded file
+struct Chunk<'a> {
+    fourcc: [u8; 4],
+    payload: &'a [u8],
+}
+
+/// Rewrite a simple or extended WebP file so it carries the given metadata chunks
+///
+/// The image bitstream is copied untouched; only the `VP8X` header and the
+/// metadata chunks are (re)built.
+pub fn mux_webp(webp: &[u8], chunks: &WebPChunks) -> Result<Vec<u8>, ProcessError> {
+    if chunks.is_empty() {
+        return Ok(webp.to_vec());
+    }
+
+    let parsed = parse_chunks(webp)?;
+
+    let mut flags = 0u8;
+    let mut canvas = None;
+    let mut image_chunks = Vec::new();
+
+    for chunk in parsed {
+        match &chunk.fourcc {
+            b"VP8X" => {
+                if chunk.payload.len() < 10 {
+                    return Err(invalid("truncated VP8X chunk"));
+                }
+                // Keep the flags we do not manage ourselves (animation)
+                flags |= chunk.payload[0] & !(VP8X_FLAG_XMP | VP8X_FLAG_EXIF | VP8X_FLAG_ICC);
+                canvas = Some((
+                    read_u24(&chunk.payload[4..7]) + 1,
+                    read_u24(&chunk.payload[7..10]) + 1,
+                ));
+            }
+            b"ICCP" | b"EXIF" | b"XMP " => {}
+            b"ALPH" => {
+                flags |= VP8X_FLAG_ALPHA;
+                image_chunks.push(chunk);
+            }
+            b"VP8L" => {
+                if canvas.is_none() {
+                    canvas = Some(vp8l_dimensions(chunk.payload)?);
+                }
+                if vp8l_has_alpha(chunk.payload) {
+                    flags |= VP8X_FLAG_ALPHA;
+                }
+                image_chunks.push(chunk);
+            }
+            b"VP8 " => {
+                if canvas.is_none() {
+                    canvas = Some(vp8_dimensions(chunk.payload)?);
+                }
+                image_chunks.push(chunk);
+            }
+            _ => image_chunks.push(chunk),
+        }
+    }
+
+    let (width, height) = canvas.ok_or_else(|| invalid("missing image bitstream"))?;
+
+    if chunks.icc_profile.is_some() {
+        flags |= VP8X_FLAG_ICC;
+    }
+    if chunks.exif.is_some() {
+        flags |= VP8X_FLAG_EXIF;
+    }
+    if chunks.xmp.is_some() {
+        flags |= VP8X_FLAG_XMP;
+    }
+
+    let mut vp8x = Vec::with_capacity(10);
+    vp8x.extend_from_slice(&[flags, 0, 0, 0]);
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    let mut body = Vec::with_capacity(webp.len());
+    body.extend_from_slice(b"WEBP");
+    write_chunk(&mut body, b"VP8X", &vp8x);
+    // The color profile must precede the image data
+    if let Some(icc_profile) = chunks.icc_profile {
+        write_chunk(&mut body, b"ICCP", icc_profile);
+    }
+    for chunk in &image_chunks {
+        write_chunk(&mut body, &chunk.fourcc, chunk.payload);
+    }
+    if let Some(exif) = chunks.exif {
+        write_chunk(&mut body, b"EXIF", exif);
+    }
+    if let Some(xmp) = chunks.xmp {
+        write_chunk(&mut body, b"XMP ", xmp);
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 8);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+
+    Ok(out)
+}
+
+/// Split a WebP file into its top-level chunks
+fn parse_chunks(webp: &[u8]) -> Result<Vec<Chunk<'_>>, ProcessError> {
+    if webp.len() < 12 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        return Err(invalid("not a WebP file"));
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= webp.len() {
+        let fourcc = [webp[pos], webp[pos + 1], webp[pos + 2], webp[pos + 3]];
+        let size = u32::from_le_bytes([webp[pos + 4], webp[pos + 5], webp[pos + 6], webp[pos + 7]])
+            as usize;
+        let start = pos + 8;
+        let end = start
+            .checked_add(size)
+            .filter(|&end| end <= webp.len())
+            .ok_or_else(|| invalid("truncated chunk"))?;
+
+        chunks.push(Chunk {
+            fourcc,
+            payload: &webp[start..end],
+        });
+
+        // Chunks are padded to an even size
+        pos = end + (size & 1);
+    }
+
+    Ok(chunks)
+}
+
+fn write_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+fn read_u24(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16
+}
+
+/// Read the frame size from a lossy (`VP8 `) bitstream header
+fn vp8_dimensions(payload: &[u8]) -> Result<(u32, u32), ProcessError> {
+    if payload.len() < 10 || payload[3..6] != [0x9d, 0x01, 0x2a] {
+        return Err(invalid("bad VP8 header"));
+    }
+    let width = u16::from_le_bytes([payload[6], payload[7]]) & 0x3fff;
+    let height = u16::from_le_bytes([payload[8], payload[9]]) & 0x3fff;
+    Ok((u32::from(width), u32::from(height)))
+}
+
+/// Read the frame size from a lossless (`VP8L`) bitstream header
+fn vp8l_dimensions(payload: &[u8]) -> Result<(u32, u32), ProcessError> {
+    if payload.len() < 5 || payload[0] != 0x2f {
+        return Err(invalid("bad VP8L header"));
+    }
+    let bits = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    Ok(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+}
+
+fn vp8l_has_alpha(payload: &[u8]) -> bool {
+    payload.len() >= 5 && payload[4] & 0x10 != 0
+}
+
+fn invalid(reason: &str) -> ProcessError {
+    ProcessError::WriteError(format!("Invalid WebP data: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+        webp::Encoder::from_rgba(rgba, width, height)
+            .encode(80.0)
+            .to_vec()
+    }
+
+    #[test]
+    fn test_mux_webp_adds_metadata_chunks() {
+        let encoded = encode(&[255; 4 * 6 * 4], 6, 4);
+        let exif = b"II*\0\x08\0\0\0\0\0";
+        let icc_profile = [1u8; 33];
+
+        let muxed = mux_webp(
+            &encoded,
+            &WebPChunks {
+                icc_profile: Some(&icc_profile),
+                exif: Some(exif),
+                xmp: None,
+            },
+        )
+        .unwrap();
+        let chunks = parse_chunks(&muxed).unwrap();
+
+        assert_eq!(&chunks[0].fourcc, b"VP8X");
+        assert_eq!(chunks[0].payload[0] & VP8X_FLAG_EXIF, VP8X_FLAG_EXIF);
+        assert_eq!(chunks[0].payload[0] & VP8X_FLAG_ICC, VP8X_FLAG_ICC);
+        assert_eq!(&chunks[1].fourcc, b"ICCP");
+        assert_eq!(chunks[1].payload, icc_profile);
+        assert_eq!(read_u24(&chunks[0].payload[4..7]) + 1, 6);
+        assert_eq!(read_u24(&chunks[0].payload[7..10]) + 1, 4);
+        assert_eq!(&chunks.last().unwrap().fourcc, b"EXIF");
+        assert_eq!(chunks.last().unwrap().payload, exif);
+
+        let decoded = image::load_from_memory(&muxed).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (6, 4));
+    }
+
+    #[test]
+    fn test_mux_webp_without_chunks_is_identity() {
+        let encoded = encode(&[0; 4 * 2 * 2], 2, 2);
+        assert_eq!(mux_webp(&encoded, &WebPChunks::default()).unwrap(), encoded);
+    }
+}
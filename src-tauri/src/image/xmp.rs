@@ -0,0 +1,178 @@
+use super::processor::ProcessError;
+
+const JPEG_XMP_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// Build an XMP packet carrying `dc:creator`, `dc:rights` and `dc:description`
+///
+/// XMP is UTF-8, so unlike EXIF ASCII fields it keeps names in any script.
+pub fn dc_packet(
+    creator: Option<&str>,
+    rights: Option<&str>,
+    description: Option<&str>,
+) -> Vec<u8> {
+    let mut properties = String::new();
+    if let Some(creator) = creator {
+        properties += &format!(
+            "   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            escape(creator)
+        );
+    }
+    for (name, value) in [("rights", rights), ("description", description)] {
+        if let Some(value) = value {
+            properties += &format!(
+                "   <dc:{name}><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:{name}>\n",
+                escape(value)
+            );
+        }
+    }
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n",
+            "{}",
+            "  </rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        properties
+    )
+    .into_bytes()
+}
+
+/// Check if a packet fits into one JPEG APP1 segment (ExtendedXMP is not written)
+pub fn fits_in_jpeg(packet: &[u8]) -> bool {
+    JPEG_XMP_NAMESPACE.len() + packet.len() + 2 <= usize::from(u16::MAX)
+}
+
+/// Insert an XMP packet as an APP1 segment after the existing APPn segments
+pub fn embed_in_jpeg(jpeg: &[u8], packet: &[u8]) -> Result<Vec<u8>, ProcessError> {
+    if jpeg.len() < 4 || jpeg[0..2] != [0xff, 0xd8] {
+        return Err(invalid("not a JPEG file"));
+    }
+    let length = JPEG_XMP_NAMESPACE.len() + packet.len() + 2;
+    let length = u16::try_from(length).map_err(|_| invalid("XMP packet too large for JPEG"))?;
+
+    // Keep JFIF/EXIF/ICC segments first, as readers expect them right after SOI
+    let mut pos = 2;
+    while pos + 4 <= jpeg.len() && jpeg[pos] == 0xff && (0xe0..=0xef).contains(&jpeg[pos + 1]) {
+        pos += 2 + u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+    }
+    if pos > jpeg.len() {
+        return Err(invalid("truncated JPEG segment"));
+    }
+
+    let mut out = Vec::with_capacity(jpeg.len() + length as usize + 2);
+    out.extend_from_slice(&jpeg[..pos]);
+    out.extend_from_slice(&[0xff, 0xe1]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(JPEG_XMP_NAMESPACE);
+    out.extend_from_slice(packet);
+    out.extend_from_slice(&jpeg[pos..]);
+
+    Ok(out)
+}
+
+/// Insert an XMP packet as an uncompressed `iTXt` chunk before the image data
+pub fn embed_in_png(png: &[u8], packet: &[u8]) -> Result<Vec<u8>, ProcessError> {
+    if png.len() < 8 || &png[1..4] != b"PNG" {
+        return Err(invalid("not a PNG file"));
+    }
+
+    let mut pos = 8;
+    loop {
+        if pos + 8 > png.len() {
+            return Err(invalid("missing IDAT chunk"));
+        }
+        if &png[pos + 4..pos + 8] == b"IDAT" {
+            break;
+        }
+        let size = u32::from_be_bytes([png[pos], png[pos + 1], png[pos + 2], png[pos + 3]]);
+        // Length, type, data and CRC
+        pos += 12 + size as usize;
+    }
+
+    // Keyword, compression flag and method, empty language tag and translated keyword
+    let mut data = Vec::with_capacity(PNG_XMP_KEYWORD.len() + 5 + packet.len());
+    data.extend_from_slice(PNG_XMP_KEYWORD);
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.extend_from_slice(packet);
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(b"iTXt");
+    chunk.extend_from_slice(&data);
+    chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+
+    let mut out = Vec::with_capacity(png.len() + chunk.len());
+    out.extend_from_slice(&png[..pos]);
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&png[pos..]);
+
+    Ok(out)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn invalid(reason: &str) -> ProcessError {
+    ProcessError::WriteError(format!("Cannot embed XMP: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, RgbImage};
+    use std::io::Cursor;
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let mut buf = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut buf, format)
+            .unwrap();
+        buf.into_inner()
+    }
+
+    fn read_xmp(data: &[u8]) -> Option<Vec<u8>> {
+        let reader = ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .unwrap();
+        reader.into_decoder().unwrap().xmp_metadata().unwrap()
+    }
+
+    #[test]
+    fn test_dc_packet_escapes_text() {
+        let packet = dc_packet(Some("田中"), Some("© 2024 A&B <Studio>"), None);
+        let packet = String::from_utf8(packet).unwrap();
+        assert!(packet.contains("<rdf:Seq><rdf:li>田中</rdf:li></rdf:Seq>"));
+        assert!(packet.contains("© 2024 A&amp;B &lt;Studio&gt;</rdf:li>"));
+        assert!(!packet.contains("dc:description"));
+    }
+
+    #[test]
+    fn test_embed_xmp_is_readable() {
+        let packet = dc_packet(None, Some("Example Rights"), None);
+
+        let jpeg = embed_in_jpeg(&encode(ImageFormat::Jpeg), &packet).unwrap();
+        assert_eq!(read_xmp(&jpeg), Some(packet.clone()));
+
+        let png = embed_in_png(&encode(ImageFormat::Png), &packet).unwrap();
+        assert_eq!(read_xmp(&png), Some(packet));
+        assert!(image::load_from_memory(&png).is_ok());
+    }
+
+    #[test]
+    fn test_fits_in_jpeg() {
+        assert!(fits_in_jpeg(&[b' '; 65_504]));
+        assert!(!fits_in_jpeg(&[b' '; 65_505]));
+        assert!(embed_in_jpeg(&encode(ImageFormat::Jpeg), &[b' '; 65_505]).is_err());
+    }
+}
@@ -16,7 +16,10 @@ pub fn run() {
             image_commands::get_image_files,
             image_commands::process_single_image,
             image_commands::process_batch,
+            image_commands::process_batch_pdf,
             image_commands::get_image_info,
+            image_commands::get_image_metadata,
+            image_commands::get_supported_formats,
             update_commands::check_for_updates,
             update_commands::get_current_version,
         ])
@@ -15,6 +15,7 @@ export function ActionButtons() {
   const {
     files,
     options,
+    pdfOptions,
     outputDir,
     processingState,
     setProcessingState,
@@ -82,6 +83,9 @@ export function ActionButtons() {
           outputPath: result.output_path,
           outputSize: result.output_size,
           reductionPercent: result.reduction_percent,
+          removedMetadata: result.removed_metadata,
+          warnings: result.warnings,
+          pageOutputs: result.page_outputs,
         });
       } else {
         updateFileStatus(result.original_path, 'error', {
@@ -100,11 +104,20 @@ export function ActionButtons() {
 
     try {
       const inputPaths = files.map((f) => f.path);
-      await invoke('process_batch', {
-        inputPaths,
-        outputDir,
-        options,
-      });
+      if (pdfOptions) {
+        await invoke('process_batch_pdf', {
+          inputPaths,
+          outputDir,
+          options,
+          pdfOptions,
+        });
+      } else {
+        await invoke('process_batch', {
+          inputPaths,
+          outputDir,
+          options,
+        });
+      }
     } catch (error) {
       console.error('Processing failed:', error);
       setError(t('errors.processingFailed') + ': ' + String(error));
@@ -5,15 +5,21 @@ import { getCurrentWindow } from '@tauri-apps/api/window';
 import { open } from '@tauri-apps/plugin-dialog';
 import { useAppStore } from '../store/useAppStore';
 import { UploadCloudIcon, FileIcon, FolderIcon } from './Icons';
-import type { FileItem } from '../types';
-
-const ACCEPTED_EXTENSIONS = ['jpg', 'jpeg', 'png', 'gif', 'bmp', 'tiff', 'tif', 'webp'];
+import type { FileItem, SupportedFormats } from '../types';
 
 export function DropZone() {
   const { t } = useTranslation();
   const { addFiles, processingState } = useAppStore();
   const [isDropped, setIsDropped] = useState(false);
   const [isDragActive, setIsDragActive] = useState(false);
+  // Extensions this build can decode (HEIC/AVIF and JPEG XL are optional backend features)
+  const [acceptedExtensions, setAcceptedExtensions] = useState<string[]>([]);
+
+  useEffect(() => {
+    invoke<SupportedFormats>('get_supported_formats')
+      .then((formats) => setAcceptedExtensions(formats.input_extensions))
+      .catch((error) => console.error('Failed to get supported formats:', error));
+  }, []);
 
   const processDroppedPaths = useCallback(
     async (paths: string[]) => {
@@ -92,7 +98,7 @@ export function DropZone() {
           filters: [
             {
               name: 'Images',
-              extensions: ACCEPTED_EXTENSIONS,
+              extensions: acceptedExtensions,
             },
           ],
         });
@@ -105,7 +111,7 @@ export function DropZone() {
         console.error('Failed to open file picker:', error);
       }
     },
-    [processingState, processDroppedPaths]
+    [processingState, processDroppedPaths, acceptedExtensions]
   );
 
   // Handle click to open folder picker
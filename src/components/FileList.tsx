@@ -1,7 +1,11 @@
+import { useState } from 'react';
 import { useTranslation } from 'react-i18next';
 import { useAppStore } from '../store/useAppStore';
+import { MetadataViewer } from './MetadataViewer';
+import { formatBytes } from './ResultsPanel';
 import {
   ImageIcon,
+  InfoIcon,
   XIcon,
   TrashIcon,
   CheckCircleIcon,
@@ -26,6 +30,7 @@ function StatusIcon({ status }: { status: FileStatus }) {
 export function FileList() {
   const { t } = useTranslation();
   const { files, removeFile, clearFiles, processingState } = useAppStore();
+  const [inspectedPath, setInspectedPath] = useState<string | null>(null);
 
   if (files.length === 0) {
     return null;
@@ -116,8 +121,12 @@ export function FileList() {
               </span>
               {file.status === 'completed' && file.reductionPercent !== undefined && (
                 <span className="text-xs text-emerald-600">
-                  → {file.outputPath?.split('/').pop()} ({file.reductionPercent.toFixed(1)}%{' '}
-                  {t('files.reduced')})
+                  → {file.outputPath?.split('/').pop()} (
+                  {file.pageOutputs && file.pageOutputs.length > 1 && file.outputSize !== undefined
+                    ? `${t('files.pages', { count: file.pageOutputs.length })}, ` +
+                      `${formatBytes(file.outputSize)}, `
+                    : ''}
+                  {file.reductionPercent.toFixed(1)}% {t('files.reduced')})
                 </span>
               )}
               {file.status === 'error' && file.error && (
@@ -125,8 +134,23 @@ export function FileList() {
                   {file.error}
                 </span>
               )}
+              {inspectedPath === file.path && <MetadataViewer path={file.path} />}
             </div>
 
+            {/* Metadata toggle */}
+            <button
+              onClick={() => setInspectedPath(inspectedPath === file.path ? null : file.path)}
+              className={`
+                p-1.5 rounded-lg
+                text-slate-400 hover:text-indigo-500 hover:bg-indigo-50
+                transition-all duration-200
+                ${inspectedPath === file.path ? 'text-indigo-500' : 'opacity-0 group-hover:opacity-100'}
+              `}
+              title={t('files.metadata')}
+            >
+              <InfoIcon className="w-4 h-4" />
+            </button>
+
             {/* Remove button - only show for pending files */}
             {file.status === 'pending' && (
               <button
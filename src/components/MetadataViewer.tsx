@@ -0,0 +1,84 @@
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { invoke } from '@tauri-apps/api/core';
+import { SpinnerIcon } from './Icons';
+import type { MetadataReport } from '../types';
+
+interface MetadataViewerProps {
+  path: string;
+}
+
+// Metadata stored in one image (EXIF, IPTC, XMP, color profile, resolution)
+export function MetadataViewer({ path }: MetadataViewerProps) {
+  const { t } = useTranslation();
+  const [report, setReport] = useState<MetadataReport | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    let cancelled = false;
+    setReport(null);
+    setError(null);
+
+    invoke<MetadataReport>('get_image_metadata', { path })
+      .then((result) => {
+        if (!cancelled) setReport(result);
+      })
+      .catch((e) => {
+        if (!cancelled) setError(String(e));
+      });
+
+    return () => {
+      cancelled = true;
+    };
+  }, [path]);
+
+  if (error) {
+    return <div className="text-xs text-rose-600 pt-2 break-all">{error}</div>;
+  }
+
+  if (!report) {
+    return (
+      <div className="flex items-center gap-2 text-xs text-slate-500 pt-2">
+        <SpinnerIcon className="w-3 h-3 animate-spin text-indigo-500" />
+        <span>{t('metadata.loading')}</span>
+      </div>
+    );
+  }
+
+  const rows: [string, string][] = [];
+  if (report.icc_description) {
+    rows.push([t('metadata.icc'), report.icc_description]);
+  }
+  if (report.dpi) {
+    rows.push([t('metadata.dpi'), `${report.dpi.x.toFixed(0)} × ${report.dpi.y.toFixed(0)}`]);
+  }
+  report.exif.forEach((entry) => rows.push([`${entry.ifd} · ${entry.tag}`, entry.value]));
+  report.iptc.forEach((entry) => rows.push([`IPTC · ${entry.name}`, entry.value]));
+
+  if (rows.length === 0 && !report.xmp) {
+    return <div className="text-xs text-slate-500 pt-2">{t('metadata.empty')}</div>;
+  }
+
+  return (
+    <div className="text-xs pt-2 space-y-2 max-h-40 overflow-y-auto custom-scrollbar animate-fadeIn">
+      {rows.length > 0 && (
+        <dl className="grid grid-cols-[auto_1fr] gap-x-3 gap-y-0.5">
+          {rows.map(([name, value], index) => (
+            <div key={index} className="contents">
+              <dt className="text-slate-500 whitespace-nowrap">{name}</dt>
+              <dd className="text-slate-700 break-all">{value}</dd>
+            </div>
+          ))}
+        </dl>
+      )}
+      {report.xmp && (
+        <details>
+          <summary className="text-slate-500 cursor-pointer">XMP</summary>
+          <pre className="mt-1 p-2 bg-slate-100 rounded-lg overflow-x-auto text-[11px] text-slate-700">
+            {report.xmp}
+          </pre>
+        </details>
+      )}
+    </div>
+  );
+}
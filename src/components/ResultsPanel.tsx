@@ -11,10 +11,12 @@ import {
 
 export function ResultsPanel() {
   const { t } = useTranslation();
-  const { progress, batchStats, processingState } = useAppStore();
+  const { progress, batchStats, processingState, files } = useAppStore();
 
   const showProgress = processingState === 'processing' && progress;
   const showResults = processingState === 'completed' && batchStats;
+  const removedMetadata = [...new Set(files.flatMap((f) => f.removedMetadata ?? []))];
+  const warnedFiles = files.filter((f) => f.warnings && f.warnings.length > 0);
 
   if (!showProgress && !showResults) {
     return null;
@@ -108,6 +110,46 @@ export function ResultsPanel() {
             </span>
           </div>
 
+          {/* PDF bundle */}
+          {batchStats.pdf_path && (
+            <div className="text-xs text-slate-500 bg-slate-50/50 rounded-xl px-4 py-2 break-all">
+              {t('results.pdf')}: {batchStats.pdf_path}
+            </div>
+          )}
+          {batchStats.pdf_error && (
+            <div className="flex items-center gap-2 text-sm text-amber-600 bg-amber-50 rounded-xl px-4 py-2.5">
+              <AlertCircleIcon className="w-4 h-4" />
+              <span className="break-all">{batchStats.pdf_error}</span>
+            </div>
+          )}
+
+          {/* Metadata removed from the outputs */}
+          {removedMetadata.length > 0 && (
+            <div className="text-xs text-slate-500 bg-slate-50/50 rounded-xl px-4 py-2">
+              {t('results.removedMetadata')}:{' '}
+              {removedMetadata.map((category) => t(`metadataCategories.${category}`)).join(', ')}
+            </div>
+          )}
+
+          {/* Warnings */}
+          {warnedFiles.length > 0 && (
+            <div className="text-sm text-amber-700 bg-amber-50 rounded-xl px-4 py-2.5 space-y-1.5">
+              <div className="flex items-center gap-2">
+                <AlertCircleIcon className="w-4 h-4" />
+                <span>{t('results.warnings', { count: warnedFiles.length })}</span>
+              </div>
+              <ul className="max-h-32 overflow-y-auto custom-scrollbar text-xs space-y-0.5">
+                {warnedFiles.flatMap((file) =>
+                  (file.warnings ?? []).map((warning) => (
+                    <li key={`${file.path}:${warning}`} className="break-all">
+                      <span className="font-medium">{file.name}</span>: {warning}
+                    </li>
+                  ))
+                )}
+              </ul>
+            </div>
+          )}
+
           {/* Error count */}
           {batchStats.failed_files > 0 && (
             <div className="flex items-center gap-2 text-sm text-rose-600 bg-rose-50 rounded-xl px-4 py-2.5">
@@ -121,7 +163,7 @@ export function ResultsPanel() {
   );
 }
 
-function formatBytes(bytes: number): string {
+export function formatBytes(bytes: number): string {
   if (bytes === 0) return '0 B';
   const k = 1024;
   const sizes = ['B', 'KB', 'MB', 'GB'];
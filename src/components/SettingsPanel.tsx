@@ -1,20 +1,120 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { useTranslation } from 'react-i18next';
+import { invoke } from '@tauri-apps/api/core';
 import { open } from '@tauri-apps/plugin-dialog';
-import { useAppStore } from '../store/useAppStore';
+import { useAppStore, defaultPdfOptions } from '../store/useAppStore';
 import { SettingsIcon, FolderIcon } from './Icons';
-import type { OutputFormat, CompressionType } from '../types';
+import type {
+  OutputFormat,
+  CompressionType,
+  ChromaSubsampling,
+  PngOptimization,
+  Dithering,
+  ColorManagement,
+  BitDepth,
+  MetadataPolicy,
+  MetadataFields,
+  PagePolicy,
+  PdfPageSize,
+  FileTimestamps,
+  SupportedFormats,
+} from '../types';
 
-const OUTPUT_FORMATS: OutputFormat[] = ['webp', 'jpeg', 'png', 'gif', 'bmp', 'tiff'];
+const OUTPUT_FORMATS: OutputFormat[] = [
+  'webp',
+  'avif',
+  'jxl',
+  'jpeg',
+  'png',
+  'gif',
+  'bmp',
+  'tiff',
+  'ico',
+  'icns',
+];
+const CHROMA_SUBSAMPLINGS: { value: ChromaSubsampling; labelKey: string }[] = [
+  { value: 'auto', labelKey: 'settings.chromaAuto' },
+  { value: '444', labelKey: 'settings.chroma444' },
+  { value: '422', labelKey: 'settings.chroma422' },
+  { value: '420', labelKey: 'settings.chroma420' },
+];
+const PNG_OPTIMIZATIONS: { value: PngOptimization; labelKey: string }[] = [
+  { value: 'off', labelKey: 'settings.pngOptimizeOff' },
+  { value: 'fast', labelKey: 'settings.pngOptimizeFast' },
+  { value: 'balanced', labelKey: 'settings.pngOptimizeBalanced' },
+  { value: 'max', labelKey: 'settings.pngOptimizeMax' },
+];
+const DITHERINGS: { value: Dithering; labelKey: string }[] = [
+  { value: 'none', labelKey: 'settings.ditherNone' },
+  { value: 'ordered', labelKey: 'settings.ditherOrdered' },
+  { value: 'floyd_steinberg', labelKey: 'settings.ditherFloydSteinberg' },
+];
+const COLOR_MANAGEMENTS: { value: ColorManagement; labelKey: string }[] = [
+  { value: 'preserve', labelKey: 'settings.colorPreserve' },
+  { value: 'convert_to_srgb', labelKey: 'settings.colorSrgb' },
+  { value: 'convert_to_srgb_untagged', labelKey: 'settings.colorSrgbUntagged' },
+];
+const BIT_DEPTHS: { value: BitDepth; labelKey: string }[] = [
+  { value: 'preserve', labelKey: 'settings.bitDepthPreserve' },
+  { value: 'force_8bit', labelKey: 'settings.bitDepthForce8' },
+];
+const METADATA_POLICIES: { value: MetadataPolicy; labelKey: string }[] = [
+  { value: 'strip_all', labelKey: 'settings.removeMetadata' },
+  { value: 'keep_all', labelKey: 'settings.keepMetadata' },
+  { value: 'keep_copyright', labelKey: 'settings.keepCopyright' },
+  { value: 'strip_private', labelKey: 'settings.stripPrivate' },
+];
+const PAGE_POLICIES: { value: PagePolicy; labelKey: string }[] = [
+  { value: 'first_page', labelKey: 'settings.firstPage' },
+  { value: 'split_pages', labelKey: 'settings.splitPages' },
+  { value: 'combine', labelKey: 'settings.combinePages' },
+];
+const FILE_TIMESTAMPS: { value: FileTimestamps; labelKey: string }[] = [
+  { value: 'processing_time', labelKey: 'settings.timestampsNow' },
+  { value: 'copy_source', labelKey: 'settings.timestampsCopy' },
+  { value: 'date_taken', labelKey: 'settings.timestampsDateTaken' },
+];
+const PDF_PAGE_SIZES: { value: PdfPageSize; labelKey: string }[] = [
+  { value: 'fit_image', labelKey: 'settings.pdfFitImage' },
+  { value: 'a4', labelKey: 'settings.pdfA4' },
+  { value: 'letter', labelKey: 'settings.pdfLetter' },
+];
+const METADATA_FIELDS: { key: keyof MetadataFields; labelKey: string }[] = [
+  { key: 'artist', labelKey: 'settings.artist' },
+  { key: 'copyright', labelKey: 'settings.copyright' },
+  { key: 'image_description', labelKey: 'settings.imageDescription' },
+  { key: 'rights', labelKey: 'settings.rights' },
+];
 
 export function SettingsPanel() {
   const { t } = useTranslation();
-  const { options, setOptions, outputDir, setOutputDir, processingState } = useAppStore();
+  const {
+    options,
+    setOptions,
+    pdfOptions,
+    setPdfOptions,
+    outputDir,
+    setOutputDir,
+    processingState,
+  } = useAppStore();
   const [resizeEnabled, setResizeEnabled] = useState(
     options.width !== null || options.height !== null
   );
+  // Formats this build can write (JPEG XL is an optional backend feature)
+  const [availableFormats, setAvailableFormats] = useState<OutputFormat[] | null>(null);
+
+  useEffect(() => {
+    invoke<SupportedFormats>('get_supported_formats')
+      .then((formats) => setAvailableFormats(formats.output_formats))
+      .catch((error) => console.error('Failed to get supported formats:', error));
+  }, []);
 
   const isProcessing = processingState === 'processing';
+  const isIconFormat = options.format === 'ico' || options.format === 'icns';
+  const usesChromaSubsampling =
+    options.format === 'jpeg' || (options.format === 'webp' && options.compression === 'lossy');
+  const usesPalette =
+    options.format === 'gif' || (options.format === 'png' && options.compression === 'lossy');
 
   const handleSelectOutputDir = async () => {
     try {
@@ -69,12 +169,49 @@ export function SettingsPanel() {
               disabled:opacity-50 disabled:cursor-not-allowed
             `}
           >
-            {OUTPUT_FORMATS.map((format) => (
+            {OUTPUT_FORMATS.filter(
+              (format) => availableFormats === null || availableFormats.includes(format)
+            ).map((format) => (
               <option key={format} value={format}>
                 {format.toUpperCase()}
               </option>
             ))}
           </select>
+          {options.format === 'jpeg' && (
+            <label className="flex items-center gap-3 cursor-pointer animate-fadeIn">
+              <input
+                type="checkbox"
+                checked={options.jpeg_backend === 'mozjpeg'}
+                onChange={(e) =>
+                  setOptions({ jpeg_backend: e.target.checked ? 'mozjpeg' : 'standard' })
+                }
+                disabled={isProcessing}
+                className="custom-checkbox"
+              />
+              <span className="text-sm font-medium text-slate-600">{t('settings.mozjpeg')}</span>
+            </label>
+          )}
+          {isIconFormat && (
+            <div className="animate-fadeIn">
+              <label className="text-xs font-medium text-slate-500 mb-1 block">
+                {t('settings.iconSizes')}
+              </label>
+              <input
+                type="text"
+                defaultValue={options.icon_sizes.join(', ')}
+                onBlur={(e) =>
+                  setOptions({
+                    icon_sizes: e.target.value
+                      .split(',')
+                      .map((size) => parseInt(size.trim()))
+                      .filter((size) => size > 0),
+                  })
+                }
+                disabled={isProcessing}
+                className="w-full custom-input bg-slate-50 border border-slate-200 rounded-xl px-3 py-2 text-sm disabled:opacity-50"
+              />
+            </div>
+          )}
         </div>
 
         {/* Quality */}
@@ -97,6 +234,94 @@ export function SettingsPanel() {
           />
         </div>
 
+        {/* Chroma Subsampling */}
+        {usesChromaSubsampling && (
+          <div className="space-y-2 animate-fadeIn">
+            <label className="text-sm font-medium text-slate-600">
+              {t('settings.chromaSubsampling')}
+            </label>
+            <select
+              value={options.chroma_subsampling}
+              onChange={(e) =>
+                setOptions({ chroma_subsampling: e.target.value as ChromaSubsampling })
+              }
+              disabled={isProcessing}
+              className={`
+                w-full custom-select
+                bg-slate-50 border border-slate-200 rounded-xl
+                px-4 py-2.5 text-sm text-slate-700 font-medium
+                transition-all duration-200
+                disabled:opacity-50 disabled:cursor-not-allowed
+              `}
+            >
+              {CHROMA_SUBSAMPLINGS.filter(
+                // Lossy WebP is always 4:2:0, so only JPEG offers the other choices
+                (subsampling) =>
+                  options.format !== 'webp' ||
+                  subsampling.value === 'auto' ||
+                  subsampling.value === '420' ||
+                  subsampling.value === options.chroma_subsampling
+              ).map((subsampling) => (
+                <option key={subsampling.value} value={subsampling.value}>
+                  {t(subsampling.labelKey)}
+                </option>
+              ))}
+            </select>
+          </div>
+        )}
+
+        {/* PNG Optimization */}
+        {options.format === 'png' && (
+          <div className="space-y-2 animate-fadeIn">
+            <label className="text-sm font-medium text-slate-600">
+              {t('settings.pngOptimization')}
+            </label>
+            <select
+              value={options.png_optimization}
+              onChange={(e) => setOptions({ png_optimization: e.target.value as PngOptimization })}
+              disabled={isProcessing}
+              className={`
+                w-full custom-select
+                bg-slate-50 border border-slate-200 rounded-xl
+                px-4 py-2.5 text-sm text-slate-700 font-medium
+                transition-all duration-200
+                disabled:opacity-50 disabled:cursor-not-allowed
+              `}
+            >
+              {PNG_OPTIMIZATIONS.map((level) => (
+                <option key={level.value} value={level.value}>
+                  {t(level.labelKey)}
+                </option>
+              ))}
+            </select>
+          </div>
+        )}
+
+        {/* Dithering */}
+        {usesPalette && (
+          <div className="space-y-2 animate-fadeIn">
+            <label className="text-sm font-medium text-slate-600">{t('settings.dithering')}</label>
+            <select
+              value={options.dithering}
+              onChange={(e) => setOptions({ dithering: e.target.value as Dithering })}
+              disabled={isProcessing}
+              className={`
+                w-full custom-select
+                bg-slate-50 border border-slate-200 rounded-xl
+                px-4 py-2.5 text-sm text-slate-700 font-medium
+                transition-all duration-200
+                disabled:opacity-50 disabled:cursor-not-allowed
+              `}
+            >
+              {DITHERINGS.map((dithering) => (
+                <option key={dithering.value} value={dithering.value}>
+                  {t(dithering.labelKey)}
+                </option>
+              ))}
+            </select>
+          </div>
+        )}
+
         {/* Resize */}
         <div className="space-y-3">
           <label className="flex items-center gap-3 cursor-pointer">
@@ -145,31 +370,171 @@ export function SettingsPanel() {
           )}
         </div>
 
+        {/* Orientation */}
+        <label className="flex items-center gap-3 cursor-pointer">
+          <input
+            type="checkbox"
+            checked={options.auto_orient}
+            onChange={(e) => setOptions({ auto_orient: e.target.checked })}
+            disabled={isProcessing}
+            className="custom-checkbox"
+          />
+          <span className="text-sm font-medium text-slate-600">{t('settings.autoOrient')}</span>
+        </label>
+
+        {/* Color Management */}
+        <div className="space-y-2">
+          <label className="text-sm font-medium text-slate-600">
+            {t('settings.colorManagement')}
+          </label>
+          <select
+            value={options.color_management}
+            onChange={(e) => setOptions({ color_management: e.target.value as ColorManagement })}
+            disabled={isProcessing}
+            className={`
+              w-full custom-select
+              bg-slate-50 border border-slate-200 rounded-xl
+              px-4 py-2.5 text-sm text-slate-700 font-medium
+              transition-all duration-200
+              disabled:opacity-50 disabled:cursor-not-allowed
+            `}
+          >
+            {COLOR_MANAGEMENTS.map((mode) => (
+              <option key={mode.value} value={mode.value}>
+                {t(mode.labelKey)}
+              </option>
+            ))}
+          </select>
+        </div>
+
+        {/* Bit Depth */}
+        <div className="space-y-2">
+          <label className="text-sm font-medium text-slate-600">{t('settings.bitDepth')}</label>
+          <select
+            value={options.bit_depth}
+            onChange={(e) => setOptions({ bit_depth: e.target.value as BitDepth })}
+            disabled={isProcessing}
+            className={`
+              w-full custom-select
+              bg-slate-50 border border-slate-200 rounded-xl
+              px-4 py-2.5 text-sm text-slate-700 font-medium
+              transition-all duration-200
+              disabled:opacity-50 disabled:cursor-not-allowed
+            `}
+          >
+            {BIT_DEPTHS.map((depth) => (
+              <option key={depth.value} value={depth.value}>
+                {t(depth.labelKey)}
+              </option>
+            ))}
+          </select>
+        </div>
+
+        {/* Camera RAW */}
+        <label className="flex items-center gap-3 cursor-pointer">
+          <input
+            type="checkbox"
+            checked={options.raw_preview}
+            onChange={(e) => setOptions({ raw_preview: e.target.checked })}
+            disabled={isProcessing}
+            className="custom-checkbox"
+          />
+          <span className="text-sm font-medium text-slate-600">{t('settings.rawPreview')}</span>
+        </label>
+
+        {/* SVG */}
+        <div className="space-y-1">
+          <label className="text-xs text-slate-500">{t('settings.svgScale')}</label>
+          <input
+            type="number"
+            min={0.1}
+            step={0.5}
+            value={options.svg_scale}
+            onChange={(e) =>
+              setOptions({ svg_scale: Math.max(0.1, parseFloat(e.target.value) || 1) })
+            }
+            disabled={isProcessing}
+            className="w-full custom-input bg-slate-50 border border-slate-200 rounded-xl px-3 py-2 text-sm disabled:opacity-50"
+          />
+        </div>
+
+        {/* Animation */}
+        <label className="flex items-center gap-3 cursor-pointer">
+          <input
+            type="checkbox"
+            checked={options.first_frame_only}
+            onChange={(e) => setOptions({ first_frame_only: e.target.checked })}
+            disabled={isProcessing}
+            className="custom-checkbox"
+          />
+          <span className="text-sm font-medium text-slate-600">
+            {t('settings.firstFrameOnly')}
+          </span>
+        </label>
+
+        {/* Multi-page TIFF */}
+        <div className="space-y-2">
+          <label className="text-sm font-medium text-slate-600">{t('settings.pages')}</label>
+          <select
+            value={options.page_policy}
+            onChange={(e) => setOptions({ page_policy: e.target.value as PagePolicy })}
+            disabled={isProcessing}
+            className={`
+              w-full custom-select
+              bg-slate-50 border border-slate-200 rounded-xl
+              px-4 py-2.5 text-sm text-slate-700 font-medium
+              transition-all duration-200
+              disabled:opacity-50 disabled:cursor-not-allowed
+            `}
+          >
+            {PAGE_POLICIES.map((policy) => (
+              <option key={policy.value} value={policy.value}>
+                {t(policy.labelKey)}
+              </option>
+            ))}
+          </select>
+        </div>
+
         {/* Metadata */}
         <div className="space-y-2">
           <label className="text-sm font-medium text-slate-600">{t('settings.metadata')}</label>
-          <div className="flex gap-4">
-            <label className="flex items-center gap-2 cursor-pointer">
-              <input
-                type="radio"
-                checked={options.keep_metadata}
-                onChange={() => setOptions({ keep_metadata: true })}
-                disabled={isProcessing}
-                className="custom-radio"
-              />
-              <span className="text-sm text-slate-700">{t('settings.keepMetadata')}</span>
-            </label>
-            <label className="flex items-center gap-2 cursor-pointer">
-              <input
-                type="radio"
-                checked={!options.keep_metadata}
-                onChange={() => setOptions({ keep_metadata: false })}
-                disabled={isProcessing}
-                className="custom-radio"
-              />
-              <span className="text-sm text-slate-700">{t('settings.removeMetadata')}</span>
-            </label>
-          </div>
+          <select
+            value={options.metadata_policy}
+            onChange={(e) => setOptions({ metadata_policy: e.target.value as MetadataPolicy })}
+            disabled={isProcessing}
+            className={`
+              w-full custom-select
+              bg-slate-50 border border-slate-200 rounded-xl
+              px-4 py-2.5 text-sm text-slate-700 font-medium
+              transition-all duration-200
+              disabled:opacity-50 disabled:cursor-not-allowed
+            `}
+          >
+            {METADATA_POLICIES.map((policy) => (
+              <option key={policy.value} value={policy.value}>
+                {t(policy.labelKey)}
+              </option>
+            ))}
+          </select>
+          {METADATA_FIELDS.map((field) => (
+            <input
+              key={field.key}
+              type="text"
+              placeholder={t(field.labelKey)}
+              value={options.metadata_fields[field.key] ?? ''}
+              onChange={(e) =>
+                setOptions({
+                  metadata_fields: {
+                    ...options.metadata_fields,
+                    [field.key]: e.target.value || null,
+                  },
+                })
+              }
+              disabled={isProcessing}
+              className="w-full custom-input bg-slate-50 border border-slate-200 rounded-xl px-3 py-2 text-sm disabled:opacity-50"
+            />
+          ))}
+          <p className="text-xs text-slate-400">{t('settings.metadataFieldsHint')}</p>
         </div>
 
         {/* Compression */}
@@ -197,6 +562,113 @@ export function SettingsPanel() {
               <span className="text-sm text-slate-700">{t('settings.lossless')}</span>
             </label>
           </div>
+          {options.format === 'png' && options.compression === 'lossy' && (
+            <p className="text-xs text-slate-400 animate-fadeIn">{t('settings.lossyPngHint')}</p>
+          )}
+        </div>
+
+        {/* File Attributes */}
+        <div className="space-y-2">
+          <label className="text-sm font-medium text-slate-600">{t('settings.timestamps')}</label>
+          <select
+            value={options.timestamps}
+            onChange={(e) => setOptions({ timestamps: e.target.value as FileTimestamps })}
+            disabled={isProcessing}
+            className={`
+              w-full custom-select
+              bg-slate-50 border border-slate-200 rounded-xl
+              px-4 py-2.5 text-sm text-slate-700 font-medium
+              transition-all duration-200
+              disabled:opacity-50 disabled:cursor-not-allowed
+            `}
+          >
+            {FILE_TIMESTAMPS.map((mode) => (
+              <option key={mode.value} value={mode.value}>
+                {t(mode.labelKey)}
+              </option>
+            ))}
+          </select>
+          <label className="flex items-center gap-3 cursor-pointer">
+            <input
+              type="checkbox"
+              checked={options.keep_permissions}
+              onChange={(e) => setOptions({ keep_permissions: e.target.checked })}
+              disabled={isProcessing}
+              className="custom-checkbox"
+            />
+            <span className="text-sm font-medium text-slate-600">
+              {t('settings.keepPermissions')}
+            </span>
+          </label>
+        </div>
+
+        {/* PDF bundle */}
+        <div className="space-y-2">
+          <label className="flex items-center gap-3 cursor-pointer">
+            <input
+              type="checkbox"
+              checked={pdfOptions !== null}
+              onChange={(e) => setPdfOptions(e.target.checked ? defaultPdfOptions : null)}
+              disabled={isProcessing}
+              className="custom-checkbox"
+            />
+            <span className="text-sm font-medium text-slate-600">{t('settings.pdfBundle')}</span>
+          </label>
+          {pdfOptions && (
+            <div className="grid grid-cols-2 gap-3">
+              <input
+                type="text"
+                value={pdfOptions.file_name}
+                onChange={(e) => setPdfOptions({ ...pdfOptions, file_name: e.target.value })}
+                disabled={isProcessing}
+                className="col-span-2 w-full custom-input bg-slate-50 border border-slate-200 rounded-xl px-3 py-2 text-sm disabled:opacity-50"
+              />
+              <select
+                value={pdfOptions.page_size}
+                onChange={(e) =>
+                  setPdfOptions({ ...pdfOptions, page_size: e.target.value as PdfPageSize })
+                }
+                disabled={isProcessing}
+                className="col-span-2 w-full custom-select bg-slate-50 border border-slate-200 rounded-xl px-4 py-2.5 text-sm text-slate-700 font-medium disabled:opacity-50"
+              >
+                {PDF_PAGE_SIZES.map((size) => (
+                  <option key={size.value} value={size.value}>
+                    {t(size.labelKey)}
+                  </option>
+                ))}
+              </select>
+              <div className="space-y-1">
+                <label className="text-xs text-slate-500">{t('settings.pdfMargin')}</label>
+                <input
+                  type="number"
+                  min={0}
+                  value={pdfOptions.margin_mm}
+                  onChange={(e) =>
+                    setPdfOptions({ ...pdfOptions, margin_mm: Number(e.target.value) || 0 })
+                  }
+                  disabled={isProcessing}
+                  className="w-full custom-input bg-slate-50 border border-slate-200 rounded-xl px-3 py-2 text-sm disabled:opacity-50"
+                />
+              </div>
+              <div className="space-y-1">
+                <label className="text-xs text-slate-500">{t('settings.pdfQuality')}</label>
+                <input
+                  type="number"
+                  min={1}
+                  max={100}
+                  value={pdfOptions.jpeg_quality}
+                  onChange={(e) =>
+                    setPdfOptions({
+                      ...pdfOptions,
+                      jpeg_quality: Math.min(100, Math.max(1, parseInt(e.target.value) || 85)),
+                    })
+                  }
+                  disabled={isProcessing}
+                  className="w-full custom-input bg-slate-50 border border-slate-200 rounded-xl px-3 py-2 text-sm disabled:opacity-50"
+                />
+              </div>
+            </div>
+          )}
         </div>
 
         {/* Output Directory */}
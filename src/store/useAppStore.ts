@@ -1,6 +1,7 @@
 import { create } from 'zustand';
 import type {
   ProcessingOptions,
+  PdfOptions,
   FileItem,
   ProcessingState,
   BatchStats,
@@ -21,6 +22,10 @@ interface AppState {
   options: ProcessingOptions;
   setOptions: (options: Partial<ProcessingOptions>) => void;
 
+  // PDF bundle of the batch (null = no bundle)
+  pdfOptions: PdfOptions | null;
+  setPdfOptions: (options: PdfOptions | null) => void;
+
   // Output directory
   outputDir: string;
   setOutputDir: (dir: string) => void;
@@ -50,8 +55,35 @@ const defaultOptions: ProcessingOptions = {
   quality: 80,
   width: null,
   height: null,
-  keep_metadata: false,
+  metadata_policy: 'strip_all',
+  metadata_fields: {
+    artist: null,
+    copyright: null,
+    image_description: null,
+    rights: null,
+  },
+  auto_orient: true,
+  color_management: 'preserve',
   compression: 'lossy',
+  jpeg_backend: 'standard',
+  chroma_subsampling: 'auto',
+  png_optimization: 'off',
+  dithering: 'floyd_steinberg',
+  bit_depth: 'preserve',
+  timestamps: 'processing_time',
+  keep_permissions: false,
+  icon_sizes: [16, 32, 48, 64, 128, 256, 512, 1024],
+  svg_scale: 1,
+  raw_preview: false,
+  first_frame_only: false,
+  page_policy: 'first_page',
+};
+
+export const defaultPdfOptions: PdfOptions = {
+  file_name: 'images.pdf',
+  page_size: 'fit_image',
+  margin_mm: 0,
+  jpeg_quality: 85,
 };
 
 export const useAppStore = create<AppState>((set) => ({
@@ -82,6 +114,9 @@ export const useAppStore = create<AppState>((set) => ({
         outputSize: undefined,
         reductionPercent: undefined,
         error: undefined,
+        removedMetadata: undefined,
+        warnings: undefined,
+        pageOutputs: undefined,
       })),
     })),
 
@@ -92,6 +127,10 @@ export const useAppStore = create<AppState>((set) => ({
       options: { ...state.options, ...newOptions },
     })),
 
+  // PDF bundle
+  pdfOptions: null,
+  setPdfOptions: (pdfOptions) => set({ pdfOptions }),
+
   // Output directory
   outputDir: '',
   setOutputDir: (dir) => set({ outputDir: dir }),
@@ -1,18 +1,116 @@
 // Image formats
-export type InputFormat = 'jpeg' | 'png' | 'gif' | 'bmp' | 'tiff' | 'webp';
-export type OutputFormat = 'jpeg' | 'png' | 'gif' | 'bmp' | 'tiff' | 'webp';
+export type InputFormat =
+  | 'jpeg'
+  | 'png'
+  | 'gif'
+  | 'bmp'
+  | 'tiff'
+  | 'webp'
+  | 'avif'
+  | 'heic'
+  | 'jxl'
+  | 'qoi'
+  | 'tga'
+  | 'pnm'
+  | 'ico'
+  | 'dds'
+  | 'hdr'
+  | 'exr'
+  | 'svg'
+  | 'raw';
+export type OutputFormat =
+  | 'jpeg'
+  | 'png'
+  | 'gif'
+  | 'bmp'
+  | 'tiff'
+  | 'webp'
+  | 'avif'
+  | 'jxl'
+  | 'ico'
+  | 'icns';
 
 // Compression type
 export type CompressionType = 'lossy' | 'lossless';
 
+// JPEG encoder
+export type JpegBackend = 'standard' | 'mozjpeg';
+
+// Chroma subsampling of JPEG and lossy WebP output
+export type ChromaSubsampling = '444' | '422' | '420' | 'auto';
+
+// Lossless optimization effort for PNG output
+export type PngOptimization = 'off' | 'fast' | 'balanced' | 'max';
+
+// Dithering of palette output (GIF, lossy PNG)
+export type Dithering = 'none' | 'ordered' | 'floyd_steinberg';
+
+// Metadata policy
+export type MetadataPolicy = 'strip_all' | 'keep_all' | 'keep_copyright' | 'strip_private';
+
+// Fields written into every output; supports {year} and {filename}
+export interface MetadataFields {
+  artist: string | null;
+  copyright: string | null;
+  image_description: string | null;
+  rights: string | null;
+}
+
+// Output bit depth
+export type BitDepth = 'preserve' | 'force_8bit';
+
+// Timestamps set on output files
+export type FileTimestamps = 'processing_time' | 'copy_source' | 'date_taken';
+
+// Color profile handling
+export type ColorManagement = 'preserve' | 'convert_to_srgb' | 'convert_to_srgb_untagged';
+
+// Handling of multi-page TIFF inputs
+export type PagePolicy = 'first_page' | 'split_pages' | 'combine';
+
+// Metadata category reported as removed
+export type MetadataCategory =
+  | 'copyright'
+  | 'gps'
+  | 'device_serial'
+  | 'exif'
+  | 'xmp'
+  | 'iptc'
+  | 'icc_profile';
+
 // Processing options
 export interface ProcessingOptions {
   format: OutputFormat;
   quality: number;
   width: number | null;
   height: number | null;
-  keep_metadata: boolean;
+  metadata_policy: MetadataPolicy;
+  metadata_fields: MetadataFields;
+  auto_orient: boolean;
+  color_management: ColorManagement;
   compression: CompressionType;
+  jpeg_backend: JpegBackend;
+  chroma_subsampling: ChromaSubsampling;
+  png_optimization: PngOptimization;
+  dithering: Dithering;
+  bit_depth: BitDepth;
+  timestamps: FileTimestamps;
+  keep_permissions: boolean;
+  icon_sizes: number[];
+  svg_scale: number;
+  raw_preview: boolean;
+  first_frame_only: boolean;
+  page_policy: PagePolicy;
+}
+
+// PDF bundle of a batch
+export type PdfPageSize = 'fit_image' | 'a4' | 'letter';
+
+export interface PdfOptions {
+  file_name: string;
+  page_size: PdfPageSize;
+  margin_mm: number;
+  jpeg_quality: number;
 }
 
 // Processing result for single image
@@ -24,6 +122,9 @@ export interface ProcessingResult {
   reduction_percent: number;
   success: boolean;
   error: string | null;
+  removed_metadata: MetadataCategory[];
+  warnings: string[];
+  page_outputs: string[];
 }
 
 // Batch processing statistics
@@ -37,6 +138,8 @@ export interface BatchStats {
   overall_reduction_percent: number;
   average_reduction_percent: number;
   median_reduction_percent: number;
+  pdf_path: string | null;
+  pdf_error: string | null;
 }
 
 // Progress update
@@ -54,6 +157,34 @@ export interface ImageInfo {
   height: number;
   size_bytes: number;
   format: string;
+  page_count: number;
+}
+
+// Formats compiled into the backend (from get_supported_formats)
+export interface SupportedFormats {
+  input_extensions: string[];
+  output_formats: OutputFormat[];
+}
+
+// Metadata stored in an image (from get_image_metadata)
+export interface ExifEntry {
+  ifd: string;
+  tag: string;
+  value: string;
+  category: MetadataCategory;
+}
+
+export interface IptcEntry {
+  name: string;
+  value: string;
+}
+
+export interface MetadataReport {
+  exif: ExifEntry[];
+  xmp: string | null;
+  iptc: IptcEntry[];
+  icc_description: string | null;
+  dpi: { x: number; y: number } | null;
 }
 
 // File processing status
@@ -70,6 +201,9 @@ export interface FileItem {
   outputSize?: number;
   reductionPercent?: number;
   error?: string;
+  removedMetadata?: MetadataCategory[];
+  warnings?: string[];
+  pageOutputs?: string[];
 }
 
 // App state